@@ -0,0 +1,232 @@
+use crate::betting::{BetRamp, BetState};
+use crate::blackjack_agent::{round_reward, Features};
+use crate::round::RoundState;
+use crate::rules::TableRules;
+use crate::shoe::Shoe;
+
+/// Plays rounds and places bets on them, so that how it does with real money can be evaluated.
+pub trait Player {
+    /// plays a round dealt from the shoe to the end
+    fn play_round(&self, shoe: &mut Shoe, rules: &TableRules, features: &Features) -> RoundState;
+}
+
+/// The variance of a round of blackjack in squared units, which the Kelly bet is divided by
+const VARIANCE: f64 = 1.33;
+
+/// How much to bet on a round, given the bankroll and the true count of the shoe before the cards are dealt.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BettingStrategy {
+    /// the same bet every round
+    Flat { bet: f64 },
+    /// one unit at a true count of 1 or less and a unit for each true count above it, up to the spread
+    Spread { unit: f64, spread: u8 },
+    /// a fraction of the bankroll that grows it fastest for the player's advantage at the true count,
+    /// the table minimum when the player has no advantage
+    Kelly { fraction: f64, minimum: f64 },
+    /// the number of units the bet agent learnt to bet at the true count
+    Learnt { unit: f64, ramp: BetRamp },
+}
+
+impl BettingStrategy {
+    /// returns the bet for the round, never more than is left in the bankroll
+    pub fn bet(&self, balance: f64, true_count: i32) -> f64 {
+        let bet = match self {
+            BettingStrategy::Flat { bet } => *bet,
+            BettingStrategy::Spread { unit, spread } => unit * true_count.clamp(1, *spread as i32) as f64,
+            BettingStrategy::Kelly { fraction, minimum } => f64::max(*minimum, fraction * balance * advantage(true_count) / VARIANCE),
+            BettingStrategy::Learnt { unit, ramp } => unit * ramp.units(true_count) as f64
+        };
+        return f64::min(bet, f64::max(balance, 0.0));
+    }
+}
+
+/// returns the player's expected return per unit bet at the true count, by the rule of thumb that
+/// the house starts with 0.5% and each true count is worth 0.5% to the player
+pub fn advantage(true_count: i32) -> f64 {
+    return 0.005 * (true_count - 1) as f64;
+}
+
+/// The money the player has, and how it went round after round.
+#[derive(Debug, Clone)]
+pub struct Bankroll {
+    /// the money left, which falls below 0 when a double, a split or insurance loses more than the bet could cover,
+    /// as the player is taken to find the money for them once the bet is down
+    pub balance: f64,
+    /// the balance after every round, starting with the initial one
+    pub trajectory: Vec<f64>,
+    /// the sum of the initial bets, before any doubles and splits
+    pub wagered: f64,
+}
+
+impl Bankroll {
+    pub fn new(balance: f64) -> Bankroll {
+        return Bankroll { balance, trajectory: vec![balance], wagered: 0.0 };
+    }
+
+    /// settles a finished round that the bet was placed on, paying doubles, splits, blackjacks
+    /// and insurance in units of the bet. Returns the amount won or lost.
+    pub fn settle(&mut self, bet: f64, round_state: &RoundState) -> f64 {
+        let won = bet * round_reward(round_state);
+        self.balance += won;
+        self.wagered += bet;
+        self.trajectory.push(self.balance);
+        return won;
+    }
+
+    /// returns how many rounds were settled
+    pub fn rounds(&self) -> usize {
+        return self.trajectory.len() - 1;
+    }
+
+    /// returns true once there is nothing left to bet, or less than nothing after losing a double or a split
+    pub fn is_ruined(&self) -> bool {
+        return self.balance <= 0.0;
+    }
+
+    /// returns the lowest the balance has been
+    pub fn lowest(&self) -> f64 {
+        return self.trajectory.iter().cloned().fold(f64::INFINITY, f64::min);
+    }
+
+    /// returns the largest fall of the balance from a previous high
+    pub fn max_drawdown(&self) -> f64 {
+        let mut peak = f64::NEG_INFINITY;
+        let mut drawdown: f64 = 0.0;
+        for balance in &self.trajectory {
+            peak = f64::max(peak, *balance);
+            drawdown = drawdown.max(peak - balance);
+        }
+        return drawdown;
+    }
+
+    pub fn print_summary(&self, strategy: &BettingStrategy) {
+        let initial = self.trajectory[0];
+        let rounds = usize::max(self.rounds(), 1);
+        println!("\nBankroll with {:?}:", strategy);
+        println!("rounds played:   {}", self.rounds());
+        println!("balance:         {:.2} (started with {:.2})", self.balance, initial);
+        println!("won per round:   {:.4}", (self.balance - initial) / rounds as f64);
+        println!("won per bet:     {:.4}", (self.balance - initial) / f64::max(self.wagered, 1.0));
+        println!("lowest balance:  {:.2}", self.lowest());
+        println!("max drawdown:    {:.2}", self.max_drawdown());
+        if self.is_ruined() {
+            println!("the bankroll was lost");
+        }
+        println!();
+    }
+}
+
+/// plays rounds from a shoe for the rules with the player, betting on each one with the betting strategy,
+/// until the rounds have been played or the bankroll is lost
+pub fn simulate(player: &dyn Player, rules: &TableRules, features: &Features, strategy: &BettingStrategy, mut bankroll: Bankroll, rounds: usize) -> Bankroll {
+    let mut shoe = Shoe::from(rules);
+    shoe.set_counting(features.counting);
+
+    for _ in 0..rounds {
+        if bankroll.is_ruined() {
+            break;
+        }
+
+        //the bet goes down before the cards are dealt, so a shoe that is due is shuffled first
+        shoe.shuffle_if_due();
+        //the true count is capped the way the bet agent saw it while learning
+        let bet = strategy.bet(bankroll.balance, BetState::from(&shoe, features).count);
+        let round_state = player.play_round(&mut shoe, rules, features);
+        bankroll.settle(bet, &round_state);
+    }
+
+    return bankroll;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::deck::Deck;
+    use crate::learner::Learner;
+    use super::*;
+
+    #[test]
+    fn test_bet_sizes() {
+        let flat = BettingStrategy::Flat { bet: 10.0 };
+        assert_eq!(10.0, flat.bet(1000.0, -3));
+        assert_eq!(10.0, flat.bet(1000.0, 5));
+        assert_eq!(4.0, flat.bet(4.0, 5));
+
+        let spread = BettingStrategy::Spread { unit: 10.0, spread: 8 };
+        assert_eq!(10.0, spread.bet(1000.0, -3));
+        assert_eq!(10.0, spread.bet(1000.0, 1));
+        assert_eq!(40.0, spread.bet(1000.0, 4));
+        assert_eq!(80.0, spread.bet(1000.0, 12));
+
+        //at a true count of 3 the player has a 1% advantage
+        let kelly = BettingStrategy::Kelly { fraction: 0.5, minimum: 5.0 };
+        assert_eq!(5.0, kelly.bet(10000.0, 0));
+        assert!((kelly.bet(13300.0, 3) - 50.0).abs() < 1e-9);
+
+        //the learnt strategy bets the units learnt at the count, one unit at a count it did not play at
+        let learnt = BettingStrategy::Learnt { unit: 10.0, ramp: BetRamp { units: BTreeMap::from([(-3, 1), (5, 6)]) } };
+        assert_eq!(10.0, learnt.bet(1000.0, -3));
+        assert_eq!(60.0, learnt.bet(1000.0, 5));
+        assert_eq!(25.0, learnt.bet(25.0, 5));
+        assert_eq!(10.0, learnt.bet(1000.0, 9));
+    }
+
+    #[test]
+    fn test_settle_in_units_of_the_bet() {
+        let mut bankroll = Bankroll::new(100.0);
+
+        let mut deck = Deck::new_rigged(&[1, 10, 9, 9, 10]);
+        let blackjack = RoundState::new(&mut deck, &TableRules::default()).unwrap();
+        assert_eq!(15.0, bankroll.settle(10.0, &blackjack));
+
+        //a doubled 11 against a 6 wins two bets
+        let mut deck = Deck::new_rigged(&[5, 6, 6, 10, 10, 10]);
+        let doubled = RoundState::new(&mut deck, &TableRules::default()).unwrap().double(&mut deck).unwrap();
+        assert_eq!(20.0, bankroll.settle(10.0, &doubled));
+
+        assert_eq!(135.0, bankroll.balance);
+        assert_eq!(vec![100.0, 115.0, 135.0], bankroll.trajectory);
+        assert_eq!(20.0, bankroll.wagered);
+        assert_eq!(2, bankroll.rounds());
+        assert_eq!(100.0, bankroll.lowest());
+    }
+
+    #[test]
+    fn test_settle_more_than_the_balance() {
+        //the whole bankroll is bet, then a 13 against a 9 is doubled and busts
+        let mut bankroll = Bankroll::new(10.0);
+        let mut deck = Deck::new_rigged(&[10, 9, 3, 10, 10, 8]);
+        let doubled = RoundState::new(&mut deck, &TableRules::default()).unwrap().double(&mut deck).unwrap();
+        assert_eq!(10.0, BettingStrategy::Flat { bet: 10.0 }.bet(bankroll.balance, 0));
+        assert_eq!(-20.0, bankroll.settle(10.0, &doubled));
+
+        assert_eq!(-10.0, bankroll.balance);
+        assert_eq!(-10.0, bankroll.lowest());
+        assert!(bankroll.is_ruined());
+        //nothing is bet once the balance is negative
+        assert_eq!(0.0, BettingStrategy::Flat { bet: 10.0 }.bet(bankroll.balance, 0));
+    }
+
+    #[test]
+    fn test_max_drawdown() {
+        let bankroll = Bankroll { balance: 90.0, trajectory: vec![100.0, 120.0, 80.0, 110.0, 90.0], wagered: 40.0 };
+        assert_eq!(40.0, bankroll.max_drawdown());
+        assert_eq!(80.0, bankroll.lowest());
+    }
+
+    #[test]
+    fn test_simulate_until_ruin() {
+        let rules = TableRules::default();
+        let bankroll = simulate(&Learner::new(), &rules, &Features::default(),
+                                &BettingStrategy::Flat { bet: 1.0 }, Bankroll::new(1000.0), 200);
+        assert_eq!(200, bankroll.rounds());
+        assert_eq!(200.0, bankroll.wagered);
+
+        //betting everything each round the bankroll does not last long
+        let bankroll = simulate(&Learner::new(), &rules, &Features::default(),
+                                &BettingStrategy::Flat { bet: 1000.0 }, Bankroll::new(10.0), 1000);
+        assert!(bankroll.is_ruined());
+        assert!(bankroll.rounds() < 1000);
+    }
+}
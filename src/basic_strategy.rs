@@ -0,0 +1,202 @@
+use crate::bankroll::Player;
+use crate::blackjack_agent::{act, available_actions, BlackjackAction, Features};
+use crate::round::{Insurance, RoundState};
+use crate::rules::{Surrender, TableRules};
+use crate::shoe::Shoe;
+
+/// The textbook multi-deck basic strategy, the best play for the hand total and the dealer's up card alone,
+/// for a game where the dealer peeks for blackjack. It never takes insurance and always bets a single unit.
+/// It is a fixed policy to compare the learnt ones against.
+#[derive(Debug, Copy, Clone)]
+pub struct BasicStrategy;
+
+/// What the chart says to do with a hand total.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Play {
+    Hit,
+    Stand,
+    /// double if allowed, hit otherwise
+    Double,
+    /// double if allowed, stand otherwise
+    DoubleOrStand,
+}
+
+impl BasicStrategy {
+    /// returns the basic strategy action for the current hand, among the actions available
+    pub fn action(round_state: &RoundState) -> BlackjackAction {
+        let actions = available_actions(round_state);
+        if actions.len() == 1 {
+            return actions[0];
+        }
+
+        let rules = &round_state.rules;
+        let player = &round_state.player;
+        let dealer = round_state.dealer.sum;
+
+        if actions.contains(&BlackjackAction::Split) {
+            let card = player.first().unwrap().value();
+            if BasicStrategy::splits(card, dealer, rules) {
+                return BlackjackAction::Split;
+            }
+        }
+
+        if actions.contains(&BlackjackAction::Surrender) && !player.ace && BasicStrategy::surrenders(player.sum, dealer, rules) {
+            return BlackjackAction::Surrender;
+        }
+
+        let play = if player.ace {
+            BasicStrategy::soft(player.sum, dealer, rules)
+        } else {
+            BasicStrategy::hard(player.sum, dealer, rules)
+        };
+        let can_double = actions.contains(&BlackjackAction::Double);
+        return match play {
+            Play::Double | Play::DoubleOrStand if can_double => BlackjackAction::Double,
+            Play::DoubleOrStand | Play::Stand => BlackjackAction::Stand,
+            Play::Hit | Play::Double if actions.contains(&BlackjackAction::Hit) => BlackjackAction::Hit,
+            _ => BlackjackAction::Stand
+        };
+    }
+
+    /// returns true if a pair of cards of the value should be split against the dealer's card
+    fn splits(card: u8, dealer: u8, rules: &TableRules) -> bool {
+        let das = rules.double_after_split;
+        return match card {
+            1 | 8 => true,
+            10 | 5 => false,
+            9 => !matches!(dealer, 7 | 10 | 11),
+            7 => dealer <= 7,
+            6 => if das { dealer <= 6 } else { (3..=6).contains(&dealer) },
+            4 => das && (5..=6).contains(&dealer),
+            _ => if das { dealer <= 7 } else { (4..=7).contains(&dealer) }
+        };
+    }
+
+    /// returns true if a hard total should be surrendered against the dealer's card
+    fn surrenders(total: u8, dealer: u8, rules: &TableRules) -> bool {
+        let h17 = rules.dealer_hits_soft_17;
+        return rules.surrender != Surrender::None && match total {
+            16 => dealer >= 9,
+            15 => dealer == 10 || (h17 && dealer == 11),
+            17 => h17 && dealer == 11,
+            _ => false
+        };
+    }
+
+    fn hard(total: u8, dealer: u8, rules: &TableRules) -> Play {
+        return match total {
+            17..=21 => Play::Stand,
+            13..=16 if dealer <= 6 => Play::Stand,
+            12 if (4..=6).contains(&dealer) => Play::Stand,
+            //against an ace 11 is only worth doubling when the dealer hits soft 17 or there are few decks
+            11 if dealer <= 10 || rules.dealer_hits_soft_17 || rules.decks <= 2 => Play::Double,
+            10 if dealer <= 9 => Play::Double,
+            9 if (3..=6).contains(&dealer) => Play::Double,
+            _ => Play::Hit
+        };
+    }
+
+    fn soft(total: u8, dealer: u8, rules: &TableRules) -> Play {
+        let h17 = rules.dealer_hits_soft_17;
+        return match total {
+            20..=21 => Play::Stand,
+            19 if h17 && dealer == 6 => Play::DoubleOrStand,
+            19 => Play::Stand,
+            18 if (3..=6).contains(&dealer) || (h17 && dealer == 2) => Play::DoubleOrStand,
+            18 if dealer <= 8 => Play::Stand,
+            17 if (3..=6).contains(&dealer) => Play::Double,
+            15..=16 if (4..=6).contains(&dealer) => Play::Double,
+            13..=14 if (5..=6).contains(&dealer) => Play::Double,
+            _ => Play::Hit
+        };
+    }
+}
+
+impl Player for BasicStrategy {
+    fn play_round(&self, shoe: &mut Shoe, rules: &TableRules, _features: &Features) -> RoundState {
+        let mut round_state = shoe.new_round(rules).unwrap();
+        if round_state.insurance == Insurance::Offered {
+            round_state = round_state.insure(false, shoe.deck()).unwrap();
+        }
+
+        while !round_state.finished() {
+            round_state = act(&round_state, BasicStrategy::action(&round_state), shoe.deck()).unwrap();
+        }
+
+        shoe.end_round(&round_state);
+        return round_state;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::deck::Deck;
+    use crate::rules::DoubleRule;
+    use super::*;
+
+    fn action(cards: &[u8], rules: &TableRules) -> BlackjackAction {
+        let mut deck = Deck::new_rigged(cards);
+        let mut round_state = RoundState::new(&mut deck, rules).unwrap();
+        if round_state.insurance == Insurance::Offered {
+            round_state = round_state.insure(false, &mut deck).unwrap();
+        }
+        return BasicStrategy::action(&round_state);
+    }
+
+    #[test]
+    fn test_hard_totals() {
+        let rules = TableRules::default();
+        assert_eq!(BlackjackAction::Stand, action(&[10, 6, 6, 10], &rules));
+        assert_eq!(BlackjackAction::Hit, action(&[10, 6, 7, 10], &rules));
+        assert_eq!(BlackjackAction::Hit, action(&[10, 2, 3, 10], &rules));
+        assert_eq!(BlackjackAction::Stand, action(&[10, 2, 4, 10], &rules));
+        assert_eq!(BlackjackAction::Double, action(&[6, 5, 1, 7], &rules));
+        assert_eq!(BlackjackAction::Double, action(&[6, 4, 9, 7], &rules));
+        assert_eq!(BlackjackAction::Hit, action(&[6, 4, 10, 7], &rules));
+        assert_eq!(BlackjackAction::Hit, action(&[5, 3, 6, 7], &rules));
+
+        //11 against an ace is only doubled with few decks, or when the dealer hits soft 17
+        let six_decks = TableRules { decks: 6, ..TableRules::default() };
+        assert_eq!(BlackjackAction::Hit, action(&[6, 5, 1, 7], &six_decks));
+        let h17 = TableRules { decks: 6, dealer_hits_soft_17: true, ..TableRules::default() };
+        assert_eq!(BlackjackAction::Double, action(&[6, 5, 1, 7], &h17));
+
+        //without doubling the 11 is hit
+        let no_double = TableRules { double: DoubleRule::Never, ..TableRules::default() };
+        assert_eq!(BlackjackAction::Hit, action(&[6, 5, 6, 7], &no_double));
+    }
+
+    #[test]
+    fn test_soft_totals() {
+        let rules = TableRules::default();
+        assert_eq!(BlackjackAction::Double, action(&[1, 7, 4, 10], &rules));
+        assert_eq!(BlackjackAction::Stand, action(&[1, 7, 2, 10], &rules));
+        assert_eq!(BlackjackAction::Stand, action(&[1, 7, 7, 10], &rules));
+        assert_eq!(BlackjackAction::Hit, action(&[1, 7, 9, 7], &rules));
+        assert_eq!(BlackjackAction::Hit, action(&[1, 2, 4, 10], &rules));
+        assert_eq!(BlackjackAction::Double, action(&[1, 2, 5, 10], &rules));
+
+        //a soft 18 that cannot be doubled stands
+        let no_double = TableRules { double: DoubleRule::TenOrEleven, ..TableRules::default() };
+        assert_eq!(BlackjackAction::Stand, action(&[1, 7, 4, 10], &no_double));
+    }
+
+    #[test]
+    fn test_pairs_and_surrender() {
+        let rules = TableRules::default();
+        assert_eq!(BlackjackAction::Split, action(&[8, 8, 10, 7], &rules));
+        assert_eq!(BlackjackAction::Split, action(&[1, 1, 10, 7], &rules));
+        assert_eq!(BlackjackAction::Stand, action(&[10, 10, 6, 10], &rules));
+        assert_eq!(BlackjackAction::Double, action(&[5, 5, 6, 10], &rules));
+        assert_eq!(BlackjackAction::Stand, action(&[9, 9, 7, 10], &rules));
+        assert_eq!(BlackjackAction::Split, action(&[9, 9, 8, 10], &rules));
+        assert_eq!(BlackjackAction::Split, action(&[4, 4, 5, 10], &rules));
+        let no_das = TableRules { double_after_split: false, ..TableRules::default() };
+        assert_eq!(BlackjackAction::Hit, action(&[4, 4, 5, 10], &no_das));
+
+        let late = TableRules { surrender: Surrender::Late, ..TableRules::default() };
+        assert_eq!(BlackjackAction::Surrender, action(&[10, 6, 10, 7], &late));
+        assert_eq!(BlackjackAction::Hit, action(&[10, 5, 9, 7], &late));
+        assert_eq!(BlackjackAction::Split, action(&[8, 8, 10, 7], &late));
+    }
+}
@@ -0,0 +1,130 @@
+use std::collections::BTreeMap;
+
+use crate::blackjack_agent::{cap_true_count, Features};
+use crate::qtable::{Action, QTable, State, StateAction};
+use crate::shoe::Shoe;
+
+/// The largest bet the agent may place, in units of the smallest one
+pub const MAX_BET: u8 = 8;
+
+/// The bankroll the bets are weighed against, in units of the smallest bet. The larger it is,
+/// the less the agent minds the swings of a large bet and the steeper the ramp it learns.
+pub const BANKROLL_UNITS: f64 = 1000.0;
+
+/// The state of the bet decision, made before the cards of a round are dealt, when all the player
+/// knows about the round is the count of the cards seen since the shoe was shuffled.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub struct BetState {
+    /// the true count of the shoe, capped like the true count of the agent state
+    pub count: i32,
+}
+
+impl State for BetState {}
+
+/// How many units to bet on the round.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub struct BetAction {
+    pub units: u8,
+}
+
+impl Action for BetAction {}
+
+/// How many units the bet agent learnt to bet at each true count it played at.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BetRamp {
+    pub units: BTreeMap<i32, u8>,
+}
+
+impl BetState {
+    /// returns the state of the shoe before the next round is dealt, which should be shuffled first if it is due
+    pub fn from(shoe: &Shoe, features: &Features) -> BetState {
+        let count = cap_true_count(shoe.true_count(features.rounding), shoe.counting, shoe.decks());
+        return BetState { count };
+    }
+}
+
+impl BetAction {
+    /// returns all the bets from 1 unit to the largest one
+    pub fn all() -> Vec<BetAction> {
+        return (1..=MAX_BET).map(|units| BetAction { units }).collect();
+    }
+}
+
+/// returns what betting the units on a round that returned the reward for each unit is worth,
+/// which is how much the log of the bankroll grows. Maximising it leads to Kelly betting, rather
+/// than to betting the most whenever the player has the slightest advantage.
+pub fn bet_utility(units: u8, reward: f64) -> f64 {
+    return f64::ln((BANKROLL_UNITS + units as f64 * reward) / BANKROLL_UNITS);
+}
+
+/// updates the values of all the bets with the sample average of their utility. The bet does not change
+/// how the cards fall, so a round tells what every bet would have returned and not just the one placed.
+pub fn learn_bets(bet_table: &mut QTable<BetState, BetAction>, bet_state: &BetState, reward: f64) {
+    for action in BetAction::all() {
+        let state_action = StateAction { agent_state: *bet_state, action };
+        let old_value = bet_table.get_value(&state_action);
+        let count = bet_table.get_count(&state_action) + 1;
+
+        let new_value = old_value + (bet_utility(action.units, reward) - old_value) / count as f64;
+        bet_table.update_value(&state_action, new_value);
+    }
+}
+
+impl BetRamp {
+    /// returns the units learnt for the true count, the smallest bet for a count that was not played at
+    pub fn units(&self, true_count: i32) -> u8 {
+        return self.units.get(&true_count).copied().unwrap_or(1);
+    }
+}
+
+/// returns the bet with the highest value, the smallest one for a count that has not been seen
+pub fn best_bet(bet_table: &QTable<BetState, BetAction>, bet_state: &BetState) -> BetAction {
+    return bet_table.select_greedy_action_among(bet_state, &BetAction::all())
+        .unwrap_or(BetAction { units: 1 });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bet_utility() {
+        assert_eq!(0.0, bet_utility(4, 0.0));
+        assert!(bet_utility(2, 1.0) > bet_utility(1, 1.0));
+        assert!(bet_utility(2, -1.0) < bet_utility(1, -1.0));
+        //a loss hurts more than the same win helps
+        assert!(bet_utility(8, 1.0) + bet_utility(8, -1.0) < 0.0);
+    }
+
+    #[test]
+    fn test_learn_bet_ramp() {
+        let mut bet_table = QTable::new(0.0);
+        let negative = BetState { count: -2 };
+        let positive = BetState { count: 4 };
+        let hot = BetState { count: 8 };
+
+        //a round at a negative count loses 2% on average, at a positive count it wins 0.2%, at a high count 5%
+        for i in 0..1000 {
+            let win = i % 2 == 0;
+            learn_bets(&mut bet_table, &negative, if win { 0.96 } else { -1.0 });
+            learn_bets(&mut bet_table, &positive, if win { 1.004 } else { -1.0 });
+            learn_bets(&mut bet_table, &hot, if win { 1.1 } else { -1.0 });
+        }
+
+        assert_eq!(1, best_bet(&bet_table, &negative).units);
+        let positive_bet = best_bet(&bet_table, &positive).units;
+        assert!(positive_bet > 1 && positive_bet < MAX_BET, "{}", positive_bet);
+        assert_eq!(MAX_BET, best_bet(&bet_table, &hot).units);
+        assert_eq!(1, best_bet(&bet_table, &BetState { count: 0 }).units);
+        assert_eq!(1000, bet_table.get_count(&StateAction { agent_state: hot, action: BetAction { units: 3 } }));
+    }
+
+    #[test]
+    fn test_bet_ramp() {
+        let ramp = BetRamp { units: BTreeMap::from([(-1, 1), (2, 3), (5, 8)]) };
+        assert_eq!(3, ramp.units(2));
+        assert_eq!(8, ramp.units(5));
+        assert_eq!(1, ramp.units(-1));
+        assert_eq!(1, ramp.units(12));
+    }
+}
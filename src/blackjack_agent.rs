@@ -1,19 +1,91 @@
 use std::collections::VecDeque;
+use crate::betting::BetState;
+use crate::counting::{CountingSystem, HiLo, Rounding};
+use crate::deck::Deck;
+use crate::insurance::{InsuranceAction, InsuranceState};
 use crate::qtable::{Action, State, StateAction};
-use crate::round::{Outcome, RoundState};
+use crate::round::{Insurance, Outcome, RoundError, RoundState};
+
+/// How many cards of each value are in a hand, from aces to tens, so that a 10-2 and a 4-4-4 can be told apart.
+pub type Composition = [u8; 10];
+
+/// The true count is capped at this, beyond it the strategy hardly changes while the states are rarely seen
+pub const MAX_TRUE_COUNT: i32 = 10;
+
+/// caps the true count at MAX_TRUE_COUNT either way. An unbalanced system's true count is its running count, which starts
+/// far below 0 in a shoe of several decks, so it is capped as far either side of the initial count as it can go over the decks.
+pub fn cap_true_count(true_count: i32, counting: &dyn CountingSystem, decks: u8) -> i32 {
+    if counting.balanced() {
+        return true_count.clamp(-MAX_TRUE_COUNT, MAX_TRUE_COUNT);
+    }
+
+    let initial = counting.initial_count(decks) / counting.scale();
+    let range = MAX_TRUE_COUNT * decks as i32;
+    return true_count.clamp(initial - range, initial + range);
+}
+
+/// Which card count, if any, the agent state includes.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum Count {
+    #[default]
+    None,
+    /// the running count
+    Running,
+    /// the true count, the running count per deck left to be seen
+    True,
+}
+
+impl Count {
+    /// returns the count of the round, with the true count rounded as specified, None if no count is kept
+    pub fn of(&self, round_state: &RoundState, rounding: Rounding) -> Option<i32> {
+        return match self {
+            Count::None => None,
+            Count::Running => Some(round_state.count),
+            Count::True => Some(cap_true_count(round_state.true_count_rounded(rounding), round_state.counting, round_state.rules.decks))
+        };
+    }
+}
+
+/// Which features of the round the agent state encodes on top of the totals, each one makes for a richer
+/// but larger state space to learn.
+#[derive(Debug, Copy, Clone)]
+pub struct Features {
+    /// the cards in the player's hand, for a composition-dependent strategy
+    pub composition: bool,
+    /// the card count, for a strategy that deviates as the shoe gets richer or poorer in tens
+    pub count: Count,
+    /// the system the cards are counted with
+    pub counting: &'static dyn CountingSystem,
+    /// how the true count is rounded
+    pub rounding: Rounding,
+}
+
+impl Default for Features {
+    fn default() -> Features {
+        return Features { composition: false, count: Count::None, counting: &HiLo, rounding: Rounding::Floor };
+    }
+}
 
 #[derive(Debug, Copy, Clone, Hash, Eq)]
+#[allow(clippy::derived_hash_with_manual_eq)]
 pub struct BlackjackState {
     pub player: u8,
     pub dealer: u8,
     pub ace: bool,
+    /// the hand is a pair that can be split
+    pub pair: bool,
+    /// the cards in the player's hand, None for a total-dependent state
+    pub composition: Option<Composition>,
+    /// the running or true count, None when the count is not kept
+    pub count: Option<i32>,
 }
 
 impl State for BlackjackState {}
 
 impl PartialEq for BlackjackState {
     fn eq(&self, other: &Self) -> bool {
-        return self.ace == other.ace && self.player == other.player && self.dealer == other.dealer;
+        return self.ace == other.ace && self.player == other.player && self.dealer == other.dealer && self.pair == other.pair
+            && self.composition == other.composition && self.count == other.count;
     }
 }
 
@@ -21,32 +93,352 @@ impl PartialEq for BlackjackState {
 pub enum BlackjackAction {
     Hit,
     Stand,
+    Double,
+    Split,
+    Surrender,
 }
 
 impl Action for BlackjackAction {}
 
 impl BlackjackState {
+    /// returns the total-dependent state of the round
     pub fn from(round_state: &RoundState) -> BlackjackState {
-        return BlackjackState { player: round_state.player.sum, ace: round_state.player.ace, dealer: round_state.dealer.sum };
+        return BlackjackState::from_features(round_state, &Features::default());
+    }
+
+    /// returns the state of the round with the specified features
+    pub fn from_features(round_state: &RoundState, features: &Features) -> BlackjackState {
+        let composition = if features.composition {
+            let mut composition = [0; 10];
+            for card in &round_state.player.cards {
+                composition[card.value() as usize - 1] += 1;
+            }
+            Some(composition)
+        } else {
+            None
+        };
+
+        return BlackjackState {
+            player: round_state.player.sum,
+            ace: round_state.player.ace,
+            dealer: round_state.dealer.sum,
+            pair: round_state.can_split(),
+            composition,
+            count: features.count.of(round_state, features.rounding),
+        };
+    }
+
+    /// returns the state with only the totals, which all compositions of the same hand total share
+    pub fn total(&self) -> BlackjackState {
+        return BlackjackState { composition: None, ..*self };
+    }
+
+    /// returns the state of basic strategy, with only the totals and no count
+    pub fn basic(&self) -> BlackjackState {
+        return BlackjackState { composition: None, count: None, ..*self };
     }
 }
 
+/// returns the cards of a composition from the highest to the lowest, e.g. "10-2" or "4-4-4"
+pub fn composition_label(composition: &Composition) -> String {
+    let mut cards = Vec::new();
+    for value in (1..=10u8).rev() {
+        let label = if value == 1 { "A".to_string() } else { value.to_string() };
+        for _ in 0..composition[value as usize - 1] {
+            cards.push(label.clone());
+        }
+    }
+    return cards.join("-");
+}
+
+/// returns the actions the agent can choose from in the current round state.
+/// Standing below 12 and hitting a 21 are never worth it, so they are left out.
+pub fn available_actions(round_state: &RoundState) -> Vec<BlackjackAction> {
+    if round_state.player.sum == 21 {
+        return vec![BlackjackAction::Stand];
+    }
+
+    let mut actions = Vec::new();
+    if round_state.can_hit() {
+        actions.push(BlackjackAction::Hit);
+    }
+    if round_state.player.sum >= 12 || !round_state.can_hit() {
+        actions.push(BlackjackAction::Stand);
+    }
+    if round_state.can_double() {
+        actions.push(BlackjackAction::Double);
+    }
+    if round_state.can_split() {
+        actions.push(BlackjackAction::Split);
+    }
+    if round_state.can_surrender() {
+        actions.push(BlackjackAction::Surrender);
+    }
+    return actions;
+}
+
+/// takes the action in the round, dealing any cards it needs from the deck
+pub fn act(round_state: &RoundState, action: BlackjackAction, deck: &mut Deck) -> Result<RoundState, RoundError> {
+    return match action {
+        BlackjackAction::Hit => round_state.hit(deck),
+        BlackjackAction::Stand => round_state.stand(deck),
+        BlackjackAction::Double => round_state.double(deck),
+        BlackjackAction::Split => round_state.split(deck),
+        BlackjackAction::Surrender => round_state.surrender(deck)
+    };
+}
+
 pub struct EpisodeResult {
     pub state_actions: VecDeque<StateAction<BlackjackState, BlackjackAction>>,
-    pub reward: i32,
+    pub reward: f64,
+    /// the insurance decision, if insurance was offered, and what the insurance returned
+    pub insurance: Option<StateAction<InsuranceState, InsuranceAction>>,
+    pub insurance_reward: f64,
+    /// the state the bet was placed in before the round was dealt
+    pub bet: BetState,
 }
 
 impl EpisodeResult {
-    pub fn from(round_state: &RoundState, state_actions: VecDeque<StateAction<BlackjackState, BlackjackAction>>) -> EpisodeResult {
-        return EpisodeResult { state_actions, reward: reward(round_state)}
+    pub fn from(round_state: &RoundState, state_actions: VecDeque<StateAction<BlackjackState, BlackjackAction>>,
+                insurance: Option<StateAction<InsuranceState, InsuranceAction>>, bet: BetState) -> EpisodeResult {
+        return EpisodeResult { state_actions, reward: reward(round_state), insurance, insurance_reward: insurance_reward(round_state), bet }
     }
+
+    /// returns the units won or lost in the round on the hands and the insurance together, what the bet is settled with
+    pub fn round_reward(&self) -> f64 {
+        return self.reward + self.insurance_reward;
+    }
+}
+
+/// returns the units won or lost in a round over all of the player's hands, a natural pays according to the table rules.
+/// The insurance is a side bet that the playing decisions do not change, so it is left out.
+pub fn reward(round_state: &RoundState) -> f64 {
+    if !round_state.finished() {
+        return 0.0;
+    }
+
+    return round_state.hands.iter()
+        .map(|played| {
+            let units = match played.outcome {
+                Outcome::Won => 1.0,
+                Outcome::Blackjack => round_state.rules.blackjack_payout,
+                Outcome::Lost => -1.0,
+                Outcome::Surrendered => -0.5,
+                Outcome::Draw => 0.0,
+                Outcome::Playing => 0.0
+            };
+            units * played.stake as f64
+        })
+        .sum::<f64>();
 }
 
-pub fn reward(round_state: &RoundState) -> i32 {
-    match round_state.outcome {
-        Outcome::Won => 1,
-        Outcome::Lost => -1,
-        Outcome::Draw => 0,
-        Outcome::Playing => 0
+/// returns the units won or lost in a round over all of the player's hands and the insurance, which is what the money changes by
+pub fn round_reward(round_state: &RoundState) -> f64 {
+    return reward(round_state) + insurance_reward(round_state);
+}
+
+/// returns the units won or lost on the insurance, which is half of the stake and pays 2:1
+pub fn insurance_reward(round_state: &RoundState) -> f64 {
+    return match round_state.insurance {
+        Insurance::Taken if round_state.insurance_won() => 1.0,
+        Insurance::Taken => -0.5,
+        _ => 0.0
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::counting::KO;
+    use crate::deck::Deck;
+    use crate::rules::{DoubleRule, Surrender, TableRules};
+    use super::*;
+
+    #[test]
+    fn test_blackjack_reward_3_to_2() {
+        let mut deck = Deck::new_rigged(&[1, 10, 9, 9, 10]);
+        let round_state = RoundState::new(&mut deck, &TableRules::default()).unwrap();
+
+        assert_eq!(reward(&round_state), 1.5);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_blackjack_reward_6_to_5() {
+        let rules = TableRules { blackjack_payout: 1.2, ..TableRules::default() };
+        let mut deck = Deck::new_rigged(&[1, 10, 9, 9, 10]);
+        let round_state = RoundState::new(&mut deck, &rules).unwrap();
+
+        assert_eq!(reward(&round_state), 1.2);
+    }
+
+    #[test]
+    fn test_double_reward() {
+        let mut deck = Deck::new_rigged(&[5, 6, 9, 10, 10, 8]);
+        let round_state = RoundState::new(&mut deck, &TableRules::default()).unwrap()
+            .double(&mut deck).unwrap();
+
+        assert_eq!(reward(&round_state), 2.0);
+    }
+
+    #[test]
+    fn test_available_actions() {
+        let mut deck = Deck::new_rigged(&[5, 4, 9, 10, 10]);
+        let round_state = RoundState::new(&mut deck, &TableRules::default()).unwrap();
+        assert_eq!(available_actions(&round_state), vec![BlackjackAction::Hit, BlackjackAction::Double]);
+
+        let after_hit = round_state.hit(&mut deck).unwrap();
+        assert_eq!(available_actions(&after_hit), vec![BlackjackAction::Hit, BlackjackAction::Stand]);
+
+        let mut deck = Deck::new_rigged(&[5, 4, 9, 10, 10]);
+        let rules = TableRules { double: DoubleRule::Never, ..TableRules::default() };
+        let round_state = RoundState::new(&mut deck, &rules).unwrap();
+        assert_eq!(available_actions(&round_state), vec![BlackjackAction::Hit]);
+    }
+
+    #[test]
+    fn test_split_reward() {
+        //the first 8 doubles to 19 and wins two units, the second 8 stands on 14 and loses one against 17
+        let mut deck = Deck::new_rigged(&[8, 8, 10, 7, 3, 8, 6, 10]);
+        let round_state = RoundState::new(&mut deck, &TableRules::default()).unwrap()
+            .split(&mut deck).unwrap()
+            .double(&mut deck).unwrap()
+            .stand(&mut deck).unwrap();
+
+        assert!(round_state.finished());
+        assert_eq!(reward(&round_state), 1.0);
+    }
+
+    #[test]
+    fn test_available_actions_pair() {
+        let mut deck = Deck::new_rigged(&[8, 8, 10, 3, 8, 10, 9, 10]);
+        let round_state = RoundState::new(&mut deck, &TableRules::default()).unwrap();
+
+        assert!(BlackjackState::from(&round_state).pair);
+        assert_eq!(available_actions(&round_state),
+                   vec![BlackjackAction::Hit, BlackjackAction::Stand, BlackjackAction::Double, BlackjackAction::Split]);
+    }
+
+    #[test]
+    fn test_composition_state() {
+        let features = Features { composition: true, ..Features::default() };
+
+        let mut deck = Deck::new_rigged(&[10, 2, 9, 7]);
+        let ten_two = RoundState::new(&mut deck, &TableRules::default()).unwrap();
+        let mut deck = Deck::new_rigged(&[4, 4, 9, 7, 4]);
+        let four_four_four = RoundState::new(&mut deck, &TableRules::default()).unwrap()
+            .hit(&mut deck).unwrap();
+
+        let ten_two_state = BlackjackState::from_features(&ten_two, &features);
+        let four_four_four_state = BlackjackState::from_features(&four_four_four, &features);
+        assert_ne!(ten_two_state, four_four_four_state);
+        assert_eq!(ten_two_state.total(), four_four_four_state.total());
+        assert_eq!(BlackjackState::from(&ten_two), ten_two_state.total());
+
+        assert_eq!("10-2", composition_label(&ten_two_state.composition.unwrap()));
+        assert_eq!("4-4-4", composition_label(&four_four_four_state.composition.unwrap()));
+    }
+
+    #[test]
+    fn test_count_state() {
+        //a 6, 5 and 4 are seen with 49 cards left to see (48 in the deck and the hole card), so the true count is 3
+        let mut cards = vec![6, 5, 4, 10];
+        cards.extend([2; 48]);
+        let mut deck = Deck::new_rigged(&cards);
+        let round_state = RoundState::new(&mut deck, &TableRules::default()).unwrap();
+
+        let running = Features { count: Count::Running, ..Features::default() };
+        let true_count = Features { count: Count::True, ..Features::default() };
+        assert_eq!(None, BlackjackState::from(&round_state).count);
+        assert_eq!(Some(3), BlackjackState::from_features(&round_state, &running).count);
+        assert_eq!(Some(3), BlackjackState::from_features(&round_state, &true_count).count);
+        assert_eq!(BlackjackState::from(&round_state), BlackjackState::from_features(&round_state, &true_count).basic());
+
+        //with only the hole card left to see, at least half a deck is taken to be left
+        let mut deck = Deck::new_rigged(&[6, 5, 4, 10]);
+        let round_state = RoundState::new(&mut deck, &TableRules::default()).unwrap();
+        assert_eq!(Some(6), BlackjackState::from_features(&round_state, &true_count).count);
+    }
+
+    #[test]
+    fn test_cap_true_count() {
+        assert_eq!(10, cap_true_count(14, &HiLo, 6));
+        assert_eq!(-10, cap_true_count(-14, &HiLo, 6));
+        assert_eq!(3, cap_true_count(3, &HiLo, 6));
+        //a six deck KO shoe starts at -20, which is not capped
+        assert_eq!(-20, cap_true_count(-20, &KO, 6));
+        assert_eq!(2, cap_true_count(2, &KO, 6));
+        assert_eq!(-80, cap_true_count(-95, &KO, 6));
+        assert_eq!(40, cap_true_count(45, &KO, 6));
+    }
+
+    #[test]
+    fn test_surrender_reward() {
+        let rules = TableRules { surrender: Surrender::Late, ..TableRules::default() };
+        let mut deck = Deck::new_rigged(&[10, 6, 10, 7, 10]);
+        let round_state = RoundState::new(&mut deck, &rules).unwrap();
+
+        assert!(available_actions(&round_state).contains(&BlackjackAction::Surrender));
+
+        let round_state = round_state.surrender(&mut deck).unwrap();
+        assert_eq!(reward(&round_state), -0.5);
+    }
+
+    #[test]
+    fn test_act() {
+        let mut deck = Deck::new_rigged(&[5, 6, 6, 10, 10, 10]);
+        let round_state = RoundState::new(&mut deck, &TableRules::default()).unwrap();
+        let doubled = act(&round_state, BlackjackAction::Double, &mut deck).unwrap();
+        assert!(doubled.finished());
+        assert_eq!(reward(&doubled), 2.0);
+
+        //an action the round does not allow is an error, as it is when called directly
+        assert!(act(&doubled, BlackjackAction::Hit, &mut deck).is_err());
+    }
+
+    #[test]
+    fn test_blackjack_push_reward() {
+        let mut deck = Deck::new_rigged(&[1, 10, 1, 10, 10]);
+        let round_state = RoundState::new(&mut deck, &TableRules::default()).unwrap()
+            .insure(false, &mut deck).unwrap();
+
+        assert!(round_state.finished());
+        assert_eq!(reward(&round_state), 0.0);
+    }
+
+    #[test]
+    fn test_insurance_reward() {
+        //the insurance pays 2:1 on half a unit, making up for the lost hand
+        let mut deck = Deck::new_rigged(&[10, 9, 1, 10, 10]);
+        let round_state = RoundState::new(&mut deck, &TableRules::default()).unwrap()
+            .insure(true, &mut deck).unwrap();
+
+        assert_eq!(insurance_reward(&round_state), 1.0);
+        assert_eq!(reward(&round_state), -1.0);
+        assert_eq!(round_reward(&round_state), 0.0);
+
+        let mut deck = Deck::new_rigged(&[10, 9, 1, 7, 10]);
+        let round_state = RoundState::new(&mut deck, &TableRules::default()).unwrap()
+            .insure(true, &mut deck).unwrap()
+            .stand(&mut deck).unwrap();
+
+        assert_eq!(insurance_reward(&round_state), -0.5);
+        assert_eq!(reward(&round_state), 1.0);
+        assert_eq!(round_reward(&round_state), 0.5);
+    }
+
+    #[test]
+    fn test_even_money() {
+        let mut deck = Deck::new_rigged(&[1, 10, 1, 7, 10]);
+        let round_state = RoundState::new(&mut deck, &TableRules::default()).unwrap()
+            .insure(true, &mut deck).unwrap();
+
+        assert_eq!(reward(&round_state), 1.5);
+        assert_eq!(round_reward(&round_state), 1.0);
+
+        let mut deck = Deck::new_rigged(&[1, 10, 1, 10, 10]);
+        let round_state = RoundState::new(&mut deck, &TableRules::default()).unwrap()
+            .insure(true, &mut deck).unwrap();
+
+        assert_eq!(reward(&round_state), 0.0);
+        assert_eq!(round_reward(&round_state), 1.0);
+    }
+}
@@ -1,54 +1,98 @@
 use rand::{Rng, thread_rng};
-use crate::blackjack_agent::{BlackjackAction, BlackjackState};
-use crate::qtable::QTable;
-
-pub fn e_greedy_policy(agent_state: &BlackjackState, q_table: &QTable<BlackjackState, BlackjackAction>, episode_number: usize) -> BlackjackAction {
-    return if agent_state.player < 12 {
-        BlackjackAction::Hit
-    } else if agent_state.player == 21 {
-        BlackjackAction::Stand
+use crate::qtable::{Action, QTable, State};
+
+/// All policies choose among the actions available in the current state,
+/// when there is only one available action it is always taken.
+pub fn e_greedy_policy<S: State, A: Action>(agent_state: &S, actions: &[A], q_table: &QTable<S, A>, episode_number: usize) -> A {
+    return if actions.len() == 1 {
+        actions[0].clone()
     } else if epsilon_explore(episode_number) {
-        random_action()
+        random_action(actions)
     } else {
-        q_table.select_greedy_action(agent_state).unwrap_or_else(|| random_action())
+        q_table.select_greedy_action_among(agent_state, actions).unwrap_or_else(|| random_action(actions))
     };
 }
 
-pub fn greedy_policy(agent_state: &BlackjackState, q_table: &QTable<BlackjackState, BlackjackAction>, _episode_number: usize) -> BlackjackAction {
-    return if agent_state.player < 12 {
-        BlackjackAction::Hit
-    } else if agent_state.player == 21 {
-        BlackjackAction::Stand
+pub fn greedy_policy<S: State, A: Action>(agent_state: &S, actions: &[A], q_table: &QTable<S, A>, _episode_number: usize) -> A {
+    return if actions.len() == 1 {
+        actions[0].clone()
     } else {
-        q_table.select_greedy_action(agent_state).unwrap_or_else(|| random_action())
+        q_table.select_greedy_action_among(agent_state, actions).unwrap_or_else(|| random_action(actions))
     };
 }
 
-pub fn random_policy(agent_state: &BlackjackState, _q_table: &QTable<BlackjackState, BlackjackAction>, _episode_number: usize) -> BlackjackAction {
-    return if agent_state.player < 12 {
-        BlackjackAction::Hit
-    } else if agent_state.player == 21 {
-        BlackjackAction::Stand
-    } else {
-        random_action()
+pub fn random_policy<S: State, A: Action>(_agent_state: &S, actions: &[A], _q_table: &QTable<S, A>, _episode_number: usize) -> A {
+    return random_action(actions);
+}
+
+/// returns the probability of each of the actions being chosen by the e_greedy_policy
+pub fn e_greedy_probabilities<S: State, A: Action>(agent_state: &S, actions: &[A], q_table: &QTable<S, A>, episode_number: usize) -> Vec<(A, f64)> {
+    if actions.len() == 1 {
+        return vec![(actions[0].clone(), 1.0)];
     }
+
+    //without a greedy action the policy is random whether it explores or not
+    return match q_table.select_greedy_action_among(agent_state, actions) {
+        Some(greedy) => {
+            let explore = epsilon(episode_number) / actions.len() as f64;
+            actions.iter()
+                .map(|action| (action.clone(), if *action == greedy { explore + 1.0 - epsilon(episode_number) } else { explore }))
+                .collect()
+        }
+        None => actions.iter().map(|action| (action.clone(), 1.0 / actions.len() as f64)).collect()
+    };
 }
 
-fn epsilon_explore(episode: usize) -> bool {
+/// returns the probability of exploring in the episode, which decays as the agent learns
+pub fn epsilon(episode: usize) -> f64 {
     //assuming the first episode is 0
 
     // let episode = 0.1;
     // let epsilon = 1.0 / (episode + 1) as f64;
-    let epsilon = f64::exp( episode as f64 / -10000.0);
+    return f64::exp( episode as f64 / -10000.0);
+}
+
+fn epsilon_explore(episode: usize) -> bool {
     //this generates a number between 0 (inclusive) and 1 (exclusive)
     let rnd = thread_rng().gen::<f64>();
 
-    return rnd < epsilon;
+    return rnd < epsilon(episode);
 }
 
-fn random_action() -> BlackjackAction {
-    match thread_rng().gen_range(0..2) {
-        0 => BlackjackAction::Hit,
-        _ => BlackjackAction::Stand
+fn random_action<A: Action>(actions: &[A]) -> A {
+    return actions[thread_rng().gen_range(0..actions.len())].clone();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::blackjack_agent::{BlackjackAction, BlackjackState};
+    use crate::qtable::StateAction;
+    use super::*;
+
+    fn assert_probabilities(expected: &[(BlackjackAction, f64)], probabilities: Vec<(BlackjackAction, f64)>) {
+        assert_eq!(expected.len(), probabilities.len());
+        for ((expected_action, expected), (action, probability)) in expected.iter().zip(probabilities) {
+            assert_eq!(*expected_action, action);
+            assert!((expected - probability).abs() < 1e-9, "{:?}: {} != {}", action, expected, probability);
+        }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_e_greedy_probabilities() {
+        let agent_state = BlackjackState { player: 16, dealer: 10, ace: false, pair: false, composition: None, count: None };
+        let actions = [BlackjackAction::Hit, BlackjackAction::Stand];
+        let mut q_table = QTable::new(0.0);
+
+        //an unseen state is played at random
+        assert_probabilities(&[(BlackjackAction::Hit, 0.5), (BlackjackAction::Stand, 0.5)], e_greedy_probabilities(&agent_state, &actions, &q_table, 100000));
+
+        q_table.update_value(&StateAction { agent_state, action: BlackjackAction::Hit }, -0.5);
+        q_table.update_value(&StateAction { agent_state, action: BlackjackAction::Stand }, -0.6);
+        let probabilities = e_greedy_probabilities(&agent_state, &actions, &q_table, 10000);
+        let explore = epsilon(10000) / 2.0;
+        assert_probabilities(&[(BlackjackAction::Hit, 1.0 - explore), (BlackjackAction::Stand, explore)], probabilities);
+
+        //a single action is always taken
+        assert_probabilities(&[(BlackjackAction::Hit, 1.0)], e_greedy_probabilities(&agent_state, &actions[..1], &q_table, 0));
+    }
+}
@@ -0,0 +1,120 @@
+use std::fmt;
+
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Rank {
+    Ace,
+    Two,
+    Three,
+    Four,
+    Five,
+    Six,
+    Seven,
+    Eight,
+    Nine,
+    Ten,
+    Jack,
+    Queen,
+    King,
+}
+
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Suit {
+    Clubs,
+    Diamonds,
+    Hearts,
+    Spades,
+}
+
+/// A playing card. Only its value counts towards the sum of a hand, but the rank and suit
+/// tell face cards and tens apart for side bets and for displaying hands.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub struct Card {
+    pub rank: Rank,
+    pub suit: Suit,
+}
+
+impl Rank {
+    pub const ALL: [Rank; 13] = [Rank::Ace, Rank::Two, Rank::Three, Rank::Four, Rank::Five, Rank::Six, Rank::Seven,
+        Rank::Eight, Rank::Nine, Rank::Ten, Rank::Jack, Rank::Queen, Rank::King];
+
+    /// returns the value of the rank, an ace is 1 and face cards are 10
+    pub fn value(&self) -> u8 {
+        return match self {
+            Rank::Jack | Rank::Queen | Rank::King => 10,
+            _ => *self as u8 + 1
+        };
+    }
+}
+
+impl Suit {
+    pub const ALL: [Suit; 4] = [Suit::Clubs, Suit::Diamonds, Suit::Hearts, Suit::Spades];
+}
+
+impl Card {
+    pub fn new(rank: Rank, suit: Suit) -> Card {
+        return Card { rank, suit };
+    }
+
+    /// returns a spade of the specified value (1 to 10), where a 10 is a ten (for testing purposes)
+    pub fn from_value(value: u8) -> Card {
+        return Card { rank: Rank::ALL[value as usize - 1], suit: Suit::Spades };
+    }
+
+    /// returns the value of the card, an ace is 1 and face cards are 10
+    pub fn value(&self) -> u8 {
+        return self.rank.value();
+    }
+
+    pub fn is_ace(&self) -> bool {
+        return self.rank == Rank::Ace;
+    }
+}
+
+impl fmt::Display for Card {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rank = match self.rank {
+            Rank::Ace => "A".to_string(),
+            Rank::Jack => "J".to_string(),
+            Rank::Queen => "Q".to_string(),
+            Rank::King => "K".to_string(),
+            _ => self.rank.value().to_string()
+        };
+        let suit = match self.suit {
+            Suit::Clubs => '♣',
+            Suit::Diamonds => '♦',
+            Suit::Hearts => '♥',
+            Suit::Spades => '♠',
+        };
+        return write!(f, "{}{}", rank, suit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_values() {
+        assert_eq!(1, Card::new(Rank::Ace, Suit::Hearts).value());
+        assert_eq!(7, Card::new(Rank::Seven, Suit::Clubs).value());
+        assert_eq!(10, Card::new(Rank::Ten, Suit::Spades).value());
+        assert_eq!(10, Card::new(Rank::Jack, Suit::Diamonds).value());
+        assert_eq!(10, Card::new(Rank::King, Suit::Hearts).value());
+    }
+
+    #[test]
+    fn test_from_value() {
+        for value in 1..=10 {
+            assert_eq!(value, Card::from_value(value).value());
+        }
+        assert_eq!(Rank::Ten, Card::from_value(10).rank);
+        assert!(Card::from_value(1).is_ace());
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!("A♠", Card::new(Rank::Ace, Suit::Spades).to_string());
+        assert_eq!("10♥", Card::new(Rank::Ten, Suit::Hearts).to_string());
+        assert_eq!("Q♦", Card::new(Rank::Queen, Suit::Diamonds).to_string());
+    }
+}
@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+
+use crate::blackjack_agent::{BlackjackAction, BlackjackState};
+use crate::qtable::{QTable, StateAction};
+
+/// A state where the best play for the cards in the hand (or for the count) differs from the best play for the hand total.
+#[derive(Debug, Copy, Clone)]
+pub struct Divergence {
+    pub state: BlackjackState,
+    /// the best action for the cards in the hand, or for the count
+    pub action: BlackjackAction,
+    /// the best action for the hand total
+    pub total_action: BlackjackAction,
+    /// how much more the composition-dependent action is expected to return each time the state is played
+    pub gain: f64,
+    /// how many times the state was played while learning
+    pub visits: usize,
+}
+
+/// returns the values of the total-dependent states, averaging the values of all the compositions with the same totals
+/// weighted by how often each was played. For a table learnt without compositions these are just its values.
+pub fn total_values(q_table: &QTable<BlackjackState, BlackjackAction>) -> HashMap<BlackjackState, HashMap<BlackjackAction, f64>> {
+    return q_table.aggregate(|state| state.total());
+}
+
+/// returns the total-dependent policy, the best action for each hand total
+pub fn total_policy(q_table: &QTable<BlackjackState, BlackjackAction>) -> HashMap<BlackjackState, BlackjackAction> {
+    return q_table.get_aggregate_policy(|state| state.total());
+}
+
+/// returns the states with a composition played at least min_visits times, whose best action is not the one
+/// the total-dependent policy would choose among the same actions. The states that gain the most over all their visits come first.
+pub fn divergences(q_table: &QTable<BlackjackState, BlackjackAction>, min_visits: usize) -> Vec<Divergence> {
+    return divergences_from(q_table, min_visits, |state| state.composition.is_some(), |state| state.total());
+}
+
+/// returns the states with a count played at least min_visits times, whose best action is not the one the policy
+/// for the hand whatever the count would choose among the same actions
+pub fn count_divergences(q_table: &QTable<BlackjackState, BlackjackAction>, min_visits: usize) -> Vec<Divergence> {
+    return divergences_from(q_table, min_visits, |state| state.count.is_some(), |state| state.basic());
+}
+
+/// returns the states kept that were played at least min_visits times, whose best action is not the one
+/// the policy of the states they are projected on would choose
+fn divergences_from<K, P>(q_table: &QTable<BlackjackState, BlackjackAction>, min_visits: usize, keep: K, project: P) -> Vec<Divergence>
+    where K: Fn(&BlackjackState) -> bool, P: Fn(&BlackjackState) -> BlackjackState {
+    let totals = q_table.aggregate(&project);
+    let mut divergences = Vec::new();
+
+    for state in q_table.get_states() {
+        if !keep(&state) {
+            continue;
+        }
+
+        let actions = q_table.get_actions(&state);
+        let value = |action: &BlackjackAction| q_table.get_value(&StateAction { agent_state: state, action: *action });
+        let visits: usize = actions.iter()
+            .map(|action| q_table.get_count(&StateAction { agent_state: state, action: *action }))
+            .sum();
+        if visits < min_visits {
+            continue;
+        }
+
+        let action = best_action(actions.iter().map(|action| (*action, value(action))));
+        let total_action = totals.get(&project(&state))
+            .and_then(|values| best_action(actions.iter().map(|action| (*action, values[action]))));
+
+        if let (Some(action), Some(total_action)) = (action, total_action) {
+            if action != total_action {
+                let gain = value(&action) - value(&total_action);
+                divergences.push(Divergence { state, action, total_action, gain, visits });
+            }
+        }
+    }
+
+    divergences.sort_by(|a, b| (b.gain * b.visits as f64).partial_cmp(&(a.gain * a.visits as f64)).unwrap());
+    return divergences;
+}
+
+fn best_action<I>(values: I) -> Option<BlackjackAction>
+    where I: Iterator<Item = (BlackjackAction, f64)> {
+    return values
+        .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+        .map(|(action, _)| action);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(composition: [u8; 10]) -> BlackjackState {
+        return BlackjackState { player: 12, dealer: 4, ace: false, pair: false, composition: Some(composition), count: None };
+    }
+
+    fn learn(q_table: &mut QTable<BlackjackState, BlackjackAction>, agent_state: BlackjackState, action: BlackjackAction, value: f64, visits: usize) {
+        for _ in 0..visits {
+            q_table.update_value(&StateAction { agent_state, action }, value);
+        }
+    }
+
+    #[test]
+    fn test_divergence_from_total_dependent_policy() {
+        let ten_two = state([0, 1, 0, 0, 0, 0, 0, 0, 0, 1]);
+        let four_four_four = state([0, 0, 0, 3, 0, 0, 0, 0, 0, 0]);
+
+        let mut q_table = QTable::new(0.0);
+        //10-2 is played far more often and should stand against a 4, 4-4-4 should hit
+        learn(&mut q_table, ten_two, BlackjackAction::Stand, -0.2, 30);
+        learn(&mut q_table, ten_two, BlackjackAction::Hit, -0.3, 30);
+        learn(&mut q_table, four_four_four, BlackjackAction::Stand, -0.25, 10);
+        learn(&mut q_table, four_four_four, BlackjackAction::Hit, -0.15, 10);
+
+        let total = ten_two.total();
+        assert_eq!(Some(&BlackjackAction::Stand), total_policy(&q_table).get(&total));
+
+        let divergences = divergences(&q_table, 1);
+        assert_eq!(1, divergences.len());
+        assert_eq!(four_four_four, divergences[0].state);
+        assert_eq!(BlackjackAction::Hit, divergences[0].action);
+        assert_eq!(BlackjackAction::Stand, divergences[0].total_action);
+        assert!((divergences[0].gain - 0.1).abs() < 1e-9);
+        assert_eq!(20, divergences[0].visits);
+
+        assert!(super::divergences(&q_table, 21).is_empty());
+    }
+
+    #[test]
+    fn test_count_divergence() {
+        let at = |count| BlackjackState { player: 16, dealer: 10, ace: false, pair: false, composition: None, count: Some(count) };
+
+        let mut q_table = QTable::new(0.0);
+        //16 against a 10 is hit at most counts, but stood on once the shoe is rich in tens
+        learn(&mut q_table, at(-1), BlackjackAction::Hit, -0.5, 40);
+        learn(&mut q_table, at(-1), BlackjackAction::Stand, -0.6, 40);
+        learn(&mut q_table, at(3), BlackjackAction::Hit, -0.6, 10);
+        learn(&mut q_table, at(3), BlackjackAction::Stand, -0.5, 10);
+
+        let divergences = count_divergences(&q_table, 1);
+        assert_eq!(1, divergences.len());
+        assert_eq!(at(3), divergences[0].state);
+        assert_eq!(BlackjackAction::Stand, divergences[0].action);
+        assert_eq!(BlackjackAction::Hit, divergences[0].total_action);
+        //without a composition there is nothing for the composition-dependent strategy to diverge on
+        assert!(super::divergences(&q_table, 1).is_empty());
+    }
+}
@@ -0,0 +1,284 @@
+use std::fmt::Debug;
+
+use crate::card::{Card, Rank};
+
+/// A card counting system, which tags every card seen with a value that is added to the running count.
+/// A balanced system's tags add up to 0 over a deck, so its running count is divided by the decks left to get
+/// the true count. An unbalanced system instead starts the running count below 0 and is played as it is.
+pub trait CountingSystem: Debug + Sync {
+    fn name(&self) -> &'static str;
+
+    /// the tags of the cards from the ace to the ten, multiplied by the scale to make them whole numbers
+    fn tags(&self) -> [i32; 10];
+
+    /// how many times the tags were multiplied to make them whole numbers (2 for half tags)
+    fn scale(&self) -> i32 {
+        return 1;
+    }
+
+    /// the value of a card added to the running count when it is seen
+    fn tag(&self, card: Card) -> i32 {
+        return self.tags()[card.value() as usize - 1];
+    }
+
+    /// returns true if the tags of a deck add up to 0
+    fn balanced(&self) -> bool {
+        return deck_total(self) == 0;
+    }
+
+    /// the running count a freshly shuffled shoe starts at. An unbalanced system starts low enough
+    /// for the count to reach its pivot as the shoe gets rich in tens, balanced ones start at 0.
+    fn initial_count(&self, decks: u8) -> i32 {
+        return -deck_total(self) * (decks as i32 - 1);
+    }
+}
+
+/// returns the sum of the tags over a deck
+fn deck_total<S: CountingSystem + ?Sized>(system: &S) -> i32 {
+    return Rank::ALL.iter()
+        .map(|rank| 4 * system.tags()[rank.value() as usize - 1])
+        .sum();
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct HiLo;
+
+impl CountingSystem for HiLo {
+    fn name(&self) -> &'static str {
+        return "Hi-Lo";
+    }
+
+    fn tags(&self) -> [i32; 10] {
+        return [-1, 1, 1, 1, 1, 1, 0, 0, 0, -1];
+    }
+}
+
+/// Knock-Out, an unbalanced Hi-Lo that also counts the 7
+#[derive(Debug, Copy, Clone)]
+pub struct KO;
+
+impl CountingSystem for KO {
+    fn name(&self) -> &'static str {
+        return "KO";
+    }
+
+    fn tags(&self) -> [i32; 10] {
+        return [-1, 1, 1, 1, 1, 1, 1, 0, 0, -1];
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct HiOptI;
+
+impl CountingSystem for HiOptI {
+    fn name(&self) -> &'static str {
+        return "Hi-Opt I";
+    }
+
+    fn tags(&self) -> [i32; 10] {
+        return [0, 0, 1, 1, 1, 1, 0, 0, 0, -1];
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct HiOptII;
+
+impl CountingSystem for HiOptII {
+    fn name(&self) -> &'static str {
+        return "Hi-Opt II";
+    }
+
+    fn tags(&self) -> [i32; 10] {
+        return [0, 1, 1, 2, 2, 1, 1, 0, 0, -2];
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct OmegaII;
+
+impl CountingSystem for OmegaII {
+    fn name(&self) -> &'static str {
+        return "Omega II";
+    }
+
+    fn tags(&self) -> [i32; 10] {
+        return [0, 1, 1, 2, 2, 2, 1, 0, -1, -2];
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Zen;
+
+impl CountingSystem for Zen {
+    fn name(&self) -> &'static str {
+        return "Zen";
+    }
+
+    fn tags(&self) -> [i32; 10] {
+        return [-1, 1, 1, 2, 2, 2, 1, 0, 0, -2];
+    }
+}
+
+/// Wong Halves, whose half tags are doubled
+#[derive(Debug, Copy, Clone)]
+pub struct WongHalves;
+
+impl CountingSystem for WongHalves {
+    fn name(&self) -> &'static str {
+        return "Wong Halves";
+    }
+
+    fn tags(&self) -> [i32; 10] {
+        return [-2, 1, 2, 2, 3, 2, 1, 0, -1, -2];
+    }
+
+    fn scale(&self) -> i32 {
+        return 2;
+    }
+}
+
+/// How the running count per deck left is rounded to a whole true count.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum Rounding {
+    /// rounded down, so a true count of +1.9 is played as +1 and one of -0.5 as -1
+    #[default]
+    Floor,
+    /// rounded towards 0, so a true count of -0.5 is played as 0
+    Truncate,
+    /// the decks left are estimated to the nearest half deck, as a player reads them off the discard tray,
+    /// and the true count is then rounded down
+    HalfDeck,
+}
+
+impl Rounding {
+    /// converts the running count of the system into a true count, dividing it by the decks left (at least half a deck).
+    /// An unbalanced count is played as it is, so it is only brought back to the scale of its tags.
+    pub fn true_count(&self, count: i32, counting: &dyn CountingSystem, cards_remaining: usize) -> i32 {
+        let count = count as f64 / counting.scale() as f64;
+        let true_count = if counting.balanced() {
+            count / f64::max(self.estimate_decks(cards_remaining), 0.5)
+        } else {
+            count
+        };
+
+        return match self {
+            Rounding::Truncate => true_count.trunc() as i32,
+            _ => true_count.floor() as i32
+        };
+    }
+
+    /// returns the decks the cards make up as the player reads them, to the nearest half deck when estimating them
+    fn estimate_decks(&self, cards_remaining: usize) -> f64 {
+        let decks = decks_remaining(cards_remaining);
+        return match self {
+            Rounding::HalfDeck => (decks * 2.0).round() / 2.0,
+            _ => decks
+        };
+    }
+}
+
+/// returns the decks the cards make up
+pub fn decks_remaining(cards_remaining: usize) -> f64 {
+    return cards_remaining as f64 / 52.0;
+}
+
+pub const SYSTEMS: [&dyn CountingSystem; 7] = [&HiLo, &KO, &HiOptI, &HiOptII, &OmegaII, &Zen, &WongHalves];
+
+/// How much removing a single card of each value from a deck changes the player's expectation (in %) playing
+/// basic strategy, from the ace to the ten (Griffin, The Theory of Blackjack)
+const EFFECTS_OF_REMOVAL: [f64; 10] = [-0.61, 0.38, 0.44, 0.55, 0.69, 0.46, 0.28, 0.00, -0.18, -0.51];
+
+/// returns the correlation between the tags of the system and the effects of removal of the cards over a deck,
+/// which tells how well the count predicts the player's advantage for betting
+pub fn betting_correlation(system: &dyn CountingSystem) -> f64 {
+    let tags = system.tags();
+    let mut tags_eor = 0.0;
+    let mut tags_squared = 0.0;
+    let mut eor_squared = 0.0;
+    for rank in Rank::ALL {
+        let i = rank.value() as usize - 1;
+        let tag = tags[i] as f64;
+        tags_eor += tag * EFFECTS_OF_REMOVAL[i];
+        tags_squared += tag * tag;
+        eor_squared += EFFECTS_OF_REMOVAL[i] * EFFECTS_OF_REMOVAL[i];
+    }
+
+    //the effects of removal average to 0 over a deck, only the tags need to be centered
+    let cards = Rank::ALL.len() as f64;
+    let tags_total = deck_total(system) as f64 / 4.0;
+    tags_squared -= tags_total * tags_total / cards;
+    return tags_eor / f64::sqrt(tags_squared * eor_squared);
+}
+
+/// prints the tags and betting correlation of all the systems
+pub fn print_counting_systems() {
+    println!("\nCounting systems:");
+    println!("system      |  A |  2 |  3 |  4 |  5 |  6 |  7 |  8 |  9 | 10 | balanced | BC");
+    println!("---------------------------------------------------------------------------------");
+    for system in SYSTEMS {
+        print!("{:<11} |", system.name());
+        for tag in system.tags() {
+            print!(" {:>+2} |", tag);
+        }
+        println!(" {:<8} | {:.3}", system.balanced(), betting_correlation(system));
+    }
+    println!("---------------------------------------------------------------------------------");
+    println!("(Wong Halves tags are doubled)");
+    println!();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::card::Suit;
+    use super::*;
+
+    #[test]
+    fn test_hilo_tags() {
+        assert_eq!(HiLo.tag(Card::from_value(2)), 1);
+        assert_eq!(HiLo.tag(Card::from_value(6)), 1);
+        assert_eq!(HiLo.tag(Card::from_value(7)), 0);
+        assert_eq!(HiLo.tag(Card::from_value(9)), 0);
+        assert_eq!(HiLo.tag(Card::from_value(10)), -1);
+        assert_eq!(HiLo.tag(Card::new(Rank::King, Suit::Hearts)), -1);
+        assert_eq!(HiLo.tag(Card::from_value(1)), -1);
+    }
+
+    #[test]
+    fn test_balanced_systems() {
+        for system in SYSTEMS {
+            assert_eq!(system.name() != "KO", system.balanced(), "{}", system.name());
+        }
+        assert_eq!(0, HiLo.initial_count(6));
+        //KO gains 4 over every deck, so six decks start at -20
+        assert_eq!(-20, KO.initial_count(6));
+        assert_eq!(0, KO.initial_count(1));
+    }
+
+    #[test]
+    fn test_true_count_rounding() {
+        //a running count of -5 with a deck and a half left is a true count of -3.33
+        assert_eq!(-4, Rounding::Floor.true_count(-5, &HiLo, 78));
+        assert_eq!(-3, Rounding::Truncate.true_count(-5, &HiLo, 78));
+        assert_eq!(-4, Rounding::HalfDeck.true_count(-5, &HiLo, 78));
+        //with 1.7 decks left the true count of +5 is +2.94, but estimated at 1.5 decks it is +3.33
+        assert_eq!(2, Rounding::Floor.true_count(5, &HiLo, 88));
+        assert_eq!(3, Rounding::HalfDeck.true_count(5, &HiLo, 88));
+        assert_eq!(1.5, Rounding::HalfDeck.estimate_decks(88));
+        assert_eq!(1.5, decks_remaining(78));
+        //at least half a deck is taken to be left
+        assert_eq!(10, Rounding::Floor.true_count(5, &HiLo, 10));
+        assert_eq!(10, Rounding::HalfDeck.true_count(5, &HiLo, 10));
+        //half tags are brought back to their scale, an unbalanced count is not divided
+        assert_eq!(2, Rounding::Floor.true_count(5, &WongHalves, 52));
+        assert_eq!(-3, Rounding::Truncate.true_count(-3, &KO, 52));
+    }
+
+    #[test]
+    fn test_betting_correlations() {
+        //the published betting correlations of these systems
+        assert!((betting_correlation(&HiLo) - 0.97).abs() < 0.01);
+        assert!((betting_correlation(&KO) - 0.98).abs() < 0.01);
+        assert!((betting_correlation(&HiOptI) - 0.88).abs() < 0.01);
+        assert!((betting_correlation(&WongHalves) - 0.99).abs() < 0.01);
+    }
+}
@@ -0,0 +1,325 @@
+use crate::deck::Deck;
+use crate::rules::{HoleCard, TableRules};
+
+/// The probability of drawing each value from an infinite deck, from the ace to the ten
+pub const INFINITE_DECK: [f64; 10] = [1.0 / 13.0, 1.0 / 13.0, 1.0 / 13.0, 1.0 / 13.0, 1.0 / 13.0,
+    1.0 / 13.0, 1.0 / 13.0, 1.0 / 13.0, 1.0 / 13.0, 4.0 / 13.0];
+
+/// The probabilities of the dealer's final hand for an up card, once the hole card and any hits have been drawn.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct DealerOutcomes {
+    /// the probability of ending on each total from 17 to 21, without a natural
+    pub totals: [f64; 5],
+    pub bust: f64,
+    pub natural: f64,
+}
+
+impl DealerOutcomes {
+    /// returns the outcomes for the up card (an ace is 11) when every card is drawn with the same probabilities,
+    /// from the ace to the ten, however many have been dealt
+    pub fn with_probabilities(up: u8, rules: &TableRules, probabilities: &[f64; 10]) -> DealerOutcomes {
+        let mut outcomes = DealerOutcomes::default();
+        let (total, soft) = add(0, false, up_value(up));
+        for (i, probability) in probabilities.iter().enumerate() {
+            let (total, soft) = add(total, soft, i as u8 + 1);
+            if total == 21 {
+                outcomes.natural += probability;
+            } else {
+                DealerOutcomes::draw_with_probabilities(total, soft, *probability, rules, probabilities, &mut outcomes);
+            }
+        }
+        return outcomes;
+    }
+
+    /// returns the outcomes for the up card (an ace is 11) from an infinite deck
+    pub fn infinite(up: u8, rules: &TableRules) -> DealerOutcomes {
+        return DealerOutcomes::with_probabilities(up, rules, &INFINITE_DECK);
+    }
+
+    /// returns the exact outcomes for the up card (an ace is 11) when the hole card and the hits are dealt from
+    /// the cards left in the deck, taking out each one drawn. Should the cards run out before the dealer is done,
+    /// those hands are left out and the probabilities add up to less than 1.
+    pub fn from_deck(up: u8, rules: &TableRules, deck: &Deck) -> DealerOutcomes {
+        let mut composition = deck.composition();
+        let mut outcomes = DealerOutcomes::default();
+        let (total, soft) = add(0, false, up_value(up));
+        let cards: usize = composition.iter().sum();
+        for i in 0..10 {
+            if composition[i] == 0 {
+                continue;
+            }
+
+            let probability = composition[i] as f64 / cards as f64;
+            let (total, soft) = add(total, soft, i as u8 + 1);
+            if total == 21 {
+                outcomes.natural += probability;
+            } else {
+                composition[i] -= 1;
+                DealerOutcomes::draw_from(total, soft, probability, rules, &mut composition, cards - 1, &mut outcomes);
+                composition[i] += 1;
+            }
+        }
+        return outcomes;
+    }
+
+    /// returns the outcomes the player faces for the rules: once a peeking dealer has checked the hole card,
+    /// the round only goes on without a natural
+    pub fn faced(up: u8, rules: &TableRules, probabilities: &[f64; 10]) -> DealerOutcomes {
+        return DealerOutcomes::with_probabilities(up, rules, probabilities).after_peek(rules);
+    }
+
+    /// like faced, but for the exact outcomes of drawing from the cards left in the deck
+    pub fn faced_from_deck(up: u8, rules: &TableRules, deck: &Deck) -> DealerOutcomes {
+        return DealerOutcomes::from_deck(up, rules, deck).after_peek(rules);
+    }
+
+    /// returns the outcomes once a dealer who peeks has checked the hole card, unchanged without a hole card
+    pub fn after_peek(&self, rules: &TableRules) -> DealerOutcomes {
+        return match rules.hole_card {
+            HoleCard::Peek => self.without_natural(),
+            HoleCard::NoHoleCard => *self
+        };
+    }
+
+    /// returns the outcomes given that the dealer does not have a natural. Should every hole card make
+    /// a natural, the dealer never goes on without one and all the probabilities are 0.
+    pub fn without_natural(&self) -> DealerOutcomes {
+        let no_natural = self.total() - self.natural;
+        if no_natural <= 0.0 {
+            return DealerOutcomes::default();
+        }
+        return DealerOutcomes { totals: self.totals.map(|total| total / no_natural), bust: self.bust / no_natural, natural: 0.0 };
+    }
+
+    /// returns the expected result of standing on the total against the dealer, -1 for a bust hand
+    pub fn stand(&self, total: u8) -> f64 {
+        if total > 21 {
+            return -1.0;
+        }
+
+        let mut value = self.bust - self.natural;
+        for (i, probability) in self.totals.iter().enumerate() {
+            let dealer = 17 + i as u8;
+            if total > dealer {
+                value += probability;
+            } else if total < dealer {
+                value -= probability;
+            }
+        }
+        return value;
+    }
+
+    /// returns the sum of the probabilities, which is 1 unless the cards can run out
+    pub fn total(&self) -> f64 {
+        return self.totals.iter().sum::<f64>() + self.bust + self.natural;
+    }
+
+    fn draw_with_probabilities(total: u8, soft: bool, probability: f64, rules: &TableRules, probabilities: &[f64; 10], outcomes: &mut DealerOutcomes) {
+        if !outcomes.settle(total, soft, probability, rules) {
+            for (i, card_probability) in probabilities.iter().enumerate() {
+                let (total, soft) = add(total, soft, i as u8 + 1);
+                DealerOutcomes::draw_with_probabilities(total, soft, probability * card_probability, rules, probabilities, outcomes);
+            }
+        }
+    }
+
+    fn draw_from(total: u8, soft: bool, probability: f64, rules: &TableRules, composition: &mut [usize; 10], cards: usize, outcomes: &mut DealerOutcomes) {
+        if outcomes.settle(total, soft, probability, rules) || cards == 0 {
+            return;
+        }
+
+        for i in 0..10 {
+            if composition[i] == 0 {
+                continue;
+            }
+
+            let card_probability = composition[i] as f64 / cards as f64;
+            let (total, soft) = add(total, soft, i as u8 + 1);
+            composition[i] -= 1;
+            DealerOutcomes::draw_from(total, soft, probability * card_probability, rules, composition, cards - 1, outcomes);
+            composition[i] += 1;
+        }
+    }
+
+    /// adds the probability of a hand the dealer stands on or busts to its outcome, returning false if the dealer draws to it
+    fn settle(&mut self, total: u8, soft: bool, probability: f64, rules: &TableRules) -> bool {
+        if total > 21 {
+            self.bust += probability;
+        } else if total > 17 || (total == 17 && !(soft && rules.dealer_hits_soft_17)) {
+            self.totals[total as usize - 17] += probability;
+        } else {
+            return false;
+        }
+        return true;
+    }
+}
+
+/// returns the total of a hand after adding a card to it, counting an ace as 11 whenever it does not bust the hand
+pub fn add(total: u8, soft: bool, card: u8) -> (u8, bool) {
+    let total = total + card;
+    return if card == 1 && total + 10 <= 21 {
+        (total + 10, true)
+    } else if total > 21 && soft {
+        (total - 10, false)
+    } else {
+        (total, soft)
+    };
+}
+
+/// the value of the up card, where an ace is shown as 11
+fn up_value(up: u8) -> u8 {
+    return if up == 11 { 1 } else { up };
+}
+
+/// prints the outcomes the player faces for each up card, from an infinite deck
+pub fn print_dealer_table(rules: &TableRules) {
+    println!("\nDealer outcomes ({}, {}):", if rules.dealer_hits_soft_17 { "H17" } else { "S17" },
+             if rules.hole_card == HoleCard::Peek { "after the peek" } else { "no hole card" });
+    println!("up |   17   |   18   |   19   |   20   |   21   |  bust  | natural");
+    println!("--------------------------------------------------------------------");
+    for up in 2u8..=11 {
+        let outcomes = DealerOutcomes::faced(up, rules, &INFINITE_DECK);
+        print!("{:>2} |", if up == 11 { "A".to_string() } else { up.to_string() });
+        for probability in outcomes.totals {
+            print!(" {:.4} |", probability);
+        }
+        println!(" {:.4} | {:.4}", outcomes.bust, outcomes.natural);
+    }
+    println!("--------------------------------------------------------------------");
+    println!();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::round::{Insurance, RoundState};
+    use super::*;
+
+    #[test]
+    fn test_infinite_deck() {
+        //the published infinite deck bust probabilities of a dealer standing on soft 17, from the 2 to the 10
+        let rules = TableRules::default();
+        let busts = [0.3536, 0.3739, 0.3941, 0.4164, 0.4232, 0.2623, 0.2447, 0.2284, 0.2121];
+        for (i, bust) in busts.iter().enumerate() {
+            let outcomes = DealerOutcomes::infinite(i as u8 + 2, &rules);
+            assert!((outcomes.bust - bust).abs() < 0.001, "{}: {}", i + 2, outcomes.bust);
+            assert!((outcomes.total() - 1.0).abs() < 1e-9);
+        }
+
+        assert!((DealerOutcomes::infinite(10, &rules).natural - 1.0 / 13.0).abs() < 1e-9);
+        assert!((DealerOutcomes::infinite(11, &rules).natural - 4.0 / 13.0).abs() < 1e-9);
+        assert_eq!(0.0, DealerOutcomes::infinite(6, &rules).natural);
+    }
+
+    #[test]
+    fn test_dealer_hits_soft_17() {
+        let s17 = DealerOutcomes::infinite(11, &TableRules::default());
+        let h17 = DealerOutcomes::infinite(11, &TableRules { dealer_hits_soft_17: true, ..TableRules::default() });
+
+        assert!(h17.totals[0] < s17.totals[0]);
+        assert!(h17.bust > s17.bust);
+        assert_eq!(s17.natural, h17.natural);
+        assert!((h17.total() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_peek() {
+        let outcomes = DealerOutcomes::infinite(10, &TableRules::default());
+        let faced = DealerOutcomes::faced(10, &TableRules::default(), &INFINITE_DECK);
+        assert_eq!(outcomes.without_natural(), faced);
+        assert_eq!(0.0, faced.natural);
+        assert!((faced.total() - 1.0).abs() < 1e-9);
+        assert!((faced.bust - outcomes.bust * 13.0 / 12.0).abs() < 1e-9);
+
+        let no_hole_card = TableRules { hole_card: HoleCard::NoHoleCard, ..TableRules::default() };
+        assert_eq!(outcomes, DealerOutcomes::faced(10, &no_hole_card, &INFINITE_DECK));
+    }
+
+    #[test]
+    fn test_peek_from_deck() {
+        let rules = TableRules::default();
+        let deck = Deck::new();
+        let outcomes = DealerOutcomes::from_deck(10, &rules, &deck);
+        let faced = DealerOutcomes::faced_from_deck(10, &rules, &deck);
+        assert_eq!(outcomes.without_natural(), faced);
+        assert_eq!(0.0, faced.natural);
+        assert!((faced.total() - 1.0).abs() < 1e-9);
+
+        let no_hole_card = TableRules { hole_card: HoleCard::NoHoleCard, ..TableRules::default() };
+        assert_eq!(outcomes, DealerOutcomes::faced_from_deck(10, &no_hole_card, &deck));
+
+        //with only tens left an ace always makes a natural, so the dealer never goes on without one
+        let tens = Deck::new_rigged(&[10; 20]);
+        let faced = DealerOutcomes::faced_from_deck(11, &rules, &tens);
+        assert_eq!(DealerOutcomes::default(), faced);
+        assert_eq!(0.0, faced.total());
+    }
+
+    #[test]
+    fn test_from_deck() {
+        //a 6 showing with a 10, 5 and 6 left: a 10 in the hole makes 16 and draws to 21 or 22,
+        //a 5 in the hole makes 11 and draws to 21 or 17, a 6 in the hole makes 12 and draws to 22 or 17
+        let deck = Deck::new_rigged(&[10, 5, 6]);
+        let outcomes = DealerOutcomes::from_deck(6, &TableRules::default(), &deck);
+
+        assert!((outcomes.totals[4] - 1.0 / 3.0).abs() < 1e-9);
+        assert!((outcomes.totals[0] - 1.0 / 3.0).abs() < 1e-9);
+        assert!((outcomes.bust - 1.0 / 3.0).abs() < 1e-9);
+        assert!((outcomes.total() - 1.0).abs() < 1e-9);
+
+        //a full deck is close to an infinite one
+        let rules = TableRules::default();
+        let full = DealerOutcomes::from_deck(6, &rules, &Deck::new());
+        assert!((full.bust - DealerOutcomes::infinite(6, &rules).bust).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_round_dealer_matches_outcomes() {
+        //the dealer of rounds dealt from shuffled decks ends up as often on each outcome as the exact probabilities
+        //for the cards left after the player's and the up card say it should
+        let rules = TableRules::default();
+        let mut expected = DealerOutcomes::default();
+        let mut seen = DealerOutcomes::default();
+        for _ in 0..3000 {
+            let mut deck = Deck::new_shuffled();
+            let mut left = deck.clone();
+            let player = (left.deal().unwrap(), left.deal().unwrap());
+            let up = left.deal().unwrap().value();
+            if (player.0.value() == 1 && player.1.value() == 10) || (player.0.value() == 10 && player.1.value() == 1) {
+                //a player natural is settled without the dealer drawing
+                continue;
+            }
+
+            let outcomes = DealerOutcomes::from_deck(if up == 1 { 11 } else { up }, &rules, &left);
+            let mut round_state = RoundState::new(&mut deck, &rules).unwrap();
+            if round_state.insurance == Insurance::Offered {
+                round_state = round_state.insure(false, &mut deck).unwrap();
+            }
+            if !round_state.finished() {
+                round_state = round_state.stand(&mut deck).unwrap();
+            }
+
+            for i in 0..5 {
+                expected.totals[i] += outcomes.totals[i];
+            }
+            expected.bust += outcomes.bust;
+            expected.natural += outcomes.natural;
+
+            let dealer = &round_state.dealer;
+            if dealer.is_natural() {
+                seen.natural += 1.0;
+            } else if dealer.is_bust() {
+                seen.bust += 1.0;
+            } else {
+                seen.totals[dealer.sum as usize - 17] += 1.0;
+            }
+        }
+
+        let mut pairs: Vec<(f64, f64)> = expected.totals.iter().copied().zip(seen.totals).collect();
+        pairs.push((expected.bust, seen.bust));
+        pairs.push((expected.natural, seen.natural));
+        for (expected, seen) in pairs {
+            //well within 5 standard deviations
+            assert!((expected - seen).abs() < 5.0 * expected.sqrt(), "expected {}, seen {}", expected, seen);
+        }
+    }
+}
@@ -4,51 +4,86 @@ use std::collections::VecDeque;
 use rand::{Rng, thread_rng};
 use rand::seq::SliceRandom;
 
+use crate::card::{Card, Rank, Suit};
+use crate::counting::decks_remaining;
+
+/// What the deck does when a card is dealt after the last one is gone.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Exhaustion {
+    /// no card is dealt
+    Fail,
+    /// the discarded cards are shuffled and dealt from, the cards still in play stay on the table
+    ReshuffleDiscards,
+}
+
 #[derive(Debug, Clone)]
 pub struct Deck {
-    cards: VecDeque<u8>
+    cards: VecDeque<Card>,
+    /// the cards dealt since they were last discarded
+    in_play: Vec<Card>,
+    discards: Vec<Card>,
+    pub exhaustion: Exhaustion,
+    /// how many times the discards were shuffled back in
+    pub reshuffles: usize,
+}
+
+impl Default for Deck {
+    fn default() -> Deck {
+        return Deck::new();
+    }
 }
 
 impl Deck {
-    /// returns a new ordered deck, face cards are represented by a ten (so each deck will contain 16 tens)
+    /// returns a new ordered deck, suit by suit from the ace to the king
     pub fn new() -> Deck {
-        return Deck{ cards: VecDeque::from(Deck::init_cards())};
+        return Deck::from_cards(VecDeque::from(Deck::init_cards(1)));
     }
 
     /// returns a new shuffled deck
     pub fn new_shuffled() -> Deck {
-        return Deck{ cards: VecDeque::from(Deck::init_cards_shuffled())};
+        return Deck::new_shuffled_decks(1);
+    }
+
+    /// returns the specified number of decks shuffled together
+    pub fn new_shuffled_decks(decks: u8) -> Deck {
+        return Deck::from_cards(VecDeque::from(Deck::init_cards_shuffled(decks)));
+    }
+
+    /// returns a new deck containing cards of the specified values in the specified order (for testing purposes)
+    pub fn new_rigged(values: &[u8]) -> Deck {
+        let mut deque = VecDeque::new();
+        deque.extend(values.iter().map(|value| Card::from_value(*value)));
+
+        return Deck::from_cards(deque);
     }
 
     /// returns a new deck containing the specified cards in the specified order (for testing purposes)
-    pub fn new_rigged(cards: &[u8]) -> Deck {
+    pub fn new_rigged_cards(cards: &[Card]) -> Deck {
         let mut deque = VecDeque::new();
         deque.extend(cards.iter().copied());
 
-        return Deck{ cards : deque };
+        return Deck::from_cards(deque);
+    }
+
+    fn from_cards(cards: VecDeque<Card>) -> Deck {
+        return Deck { cards, in_play: Vec::new(), discards: Vec::new(), exhaustion: Exhaustion::Fail, reshuffles: 0 };
     }
 
-    /// returns a vector of all cards in a deck shuffled
-    fn init_cards_shuffled() -> Vec<u8> {
-        let mut cards = Deck::init_cards();
+    /// returns a vector of all cards in the specified number of decks shuffled
+    fn init_cards_shuffled(decks: u8) -> Vec<Card> {
+        let mut cards = Deck::init_cards(decks);
         cards.shuffle(&mut thread_rng());
         return cards;
     }
 
-    /// returns a vector of all cards in a deck in order
-    fn init_cards() -> Vec<u8> {
-        let mut cards : Vec<u8> = Vec::new();
+    /// returns a vector of all cards in the specified number of decks in order
+    fn init_cards(decks: u8) -> Vec<Card> {
+        let mut cards : Vec<Card> = Vec::new();
 
-        for _ in 1..=4 {
-            for card in 1..=10 {
-                if card == 10 {
-                    //there are 3 face cards apart from a 10 that also count as a 10 for each suit
-                    for _ in 1..=4 {
-                        cards.push(card);
-                    }
-                }
-                else {
-                    cards.push(card);
+        for _ in 1..=decks {
+            for suit in Suit::ALL {
+                for rank in Rank::ALL {
+                    cards.push(Card::new(rank, suit));
                 }
             }
         }
@@ -56,9 +91,35 @@ impl Deck {
         return cards;
     }
 
-    /// deals a card from the deck
-    pub fn deal(&mut self) -> Option<u8> {
-        return self.cards.pop_front();
+    /// deals a card from the deck, shuffling the discards back in first if the deck is exhausted and the policy allows it
+    pub fn deal(&mut self) -> Option<Card> {
+        if self.cards.is_empty() && self.exhaustion == Exhaustion::ReshuffleDiscards && !self.discards.is_empty() {
+            self.reshuffle_discards();
+        }
+
+        let card = self.cards.pop_front()?;
+        self.in_play.push(card);
+        return Some(card);
+    }
+
+    /// moves the cards dealt so far to the discards, once they are no longer in play at the end of a round
+    pub fn discard(&mut self) {
+        self.discards.append(&mut self.in_play);
+    }
+
+    /// puts the cards dealt so far back into the deck at random positions, like a continuous shuffling machine
+    pub fn reinsert(&mut self) {
+        let mut rng = thread_rng();
+        for card in self.in_play.drain(..) {
+            let position = rng.gen_range(0..=self.cards.len());
+            self.cards.insert(position, card);
+        }
+    }
+
+    fn reshuffle_discards(&mut self) {
+        self.cards.extend(self.discards.drain(..));
+        self.shuffle();
+        self.reshuffles += 1;
     }
 
     /// shuffles the deck in-place
@@ -77,6 +138,25 @@ impl Deck {
     pub fn len(&self) -> usize {
         return self.cards.len();
     }
+
+    /// returns how many cards of each value are left, from the ace to the ten
+    pub fn composition(&self) -> [usize; 10] {
+        let mut composition = [0; 10];
+        for card in &self.cards {
+            composition[card.value() as usize - 1] += 1;
+        }
+        return composition;
+    }
+
+    /// returns how many decks the cards left make up
+    pub fn decks_remaining(&self) -> f64 {
+        return decks_remaining(self.len());
+    }
+
+    /// returns true if there are no cards left
+    pub fn is_empty(&self) -> bool {
+        return self.cards.is_empty();
+    }
 }
 
 #[cfg(test)]
@@ -90,6 +170,18 @@ mod tests {
         assert_eq!(52, deck.len());
     }
 
+    #[test]
+    fn test_multiple_decks_size() {
+        let deck = Deck::new_shuffled_decks(6);
+
+        assert_eq!(6 * 52, deck.len());
+        assert_eq!(6 * 4, deck.cards.iter().filter(|card| card.value() == 1).count());
+        assert_eq!(6 * 16, deck.cards.iter().filter(|card| card.value() == 10).count());
+        assert_eq!(6 * 4, deck.cards.iter().filter(|card| card.rank == Rank::King).count());
+        assert_eq!(6 * 13, deck.cards.iter().filter(|card| card.suit == Suit::Hearts).count());
+        assert_eq!([24, 24, 24, 24, 24, 24, 24, 24, 24, 96], deck.composition());
+    }
+
     #[test]
     fn test_new_deck_cards() {
         let deck = Deck::new();
@@ -98,21 +190,62 @@ mod tests {
             let rem = (i % 13) + 1;
             let expected = if rem > 10 { 10 } else { rem } as u8;
 
-            assert_eq!(expected, *card);
+            assert_eq!(expected, card.value());
         }
     }
 
     #[test]
+    #[allow(clippy::needless_range_loop)]
     fn test_new_rigged_deck() {
         let cards:[u8; 5] = [1, 2, 3, 4, 5];
         let mut deck = Deck::new_rigged(&cards);
 
         assert_eq!(deck.len(), cards.len());
         for i in 0 .. cards.len() {
-            assert_eq!(deck.deal().unwrap(), cards[i]);
+            assert_eq!(deck.deal().unwrap(), Card::from_value(cards[i]));
         }
     }
 
+    #[test]
+    fn test_exhausted_deck_fails() {
+        let mut deck = Deck::new_rigged(&[1, 2]);
+        deck.deal();
+        deck.discard();
+        deck.deal();
+
+        assert_eq!(None, deck.deal());
+    }
+
+    #[test]
+    fn test_exhausted_deck_reshuffles_discards() {
+        let mut deck = Deck::new_rigged(&[1, 2, 3]);
+        deck.exhaustion = Exhaustion::ReshuffleDiscards;
+        deck.deal();
+        deck.deal();
+        deck.discard();
+        assert_eq!(Some(Card::from_value(3)), deck.deal());
+
+        //only the discarded cards come back, the 3 is still in play
+        let mut reshuffled = vec![deck.deal().unwrap().value(), deck.deal().unwrap().value()];
+        reshuffled.sort();
+        assert_eq!(vec![1, 2], reshuffled);
+        assert_eq!(1, deck.reshuffles);
+        assert_eq!(None, deck.deal());
+    }
+
+    #[test]
+    fn test_reinsert() {
+        let mut deck = Deck::new_shuffled();
+        for _ in 0..5 {
+            deck.deal();
+        }
+        deck.reinsert();
+
+        assert_eq!(52, deck.len());
+        assert_eq!(4, deck.cards.iter().filter(|card| card.value() == 1).count());
+        assert_eq!(16, deck.cards.iter().filter(|card| card.value() == 10).count());
+    }
+
     #[test]
     fn test_deal() {
         let mut deck = Deck::new_shuffled();
@@ -143,7 +276,7 @@ mod tests {
         assert!(out_of_place > 0);
     }
 
-    fn count_out_of_place(v1 : &VecDeque<u8>, v2: &VecDeque<u8>) -> usize {
+    fn count_out_of_place(v1 : &VecDeque<Card>, v2: &VecDeque<Card>) -> usize {
         let mut out_of_place = 0;
         for i in 0 .. v1.len() {
             if v1[i] != v2[i] {
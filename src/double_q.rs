@@ -0,0 +1,122 @@
+use crate::betting::learn_bets;
+use crate::blackjack_agent::{BlackjackAction, BlackjackState, Features};
+use crate::blackjack_policy::greedy_policy;
+use crate::learner::Learner;
+use crate::qtable::{QTable, StateAction};
+use crate::rules::TableRules;
+use crate::sarsa::{episode, sarsamax, Mode};
+use crate::shoe::Shoe;
+use crate::solver::InfiniteDeckSolver;
+
+/// Double Q-learning keeps two estimates of the values, which take turns being updated an episode each.
+/// The best next action is picked with the estimate being updated and valued with the other one, so that
+/// the noise of the returns no longer makes the best action look better than it is, as it does with Q-learning.
+/// The learner's table holds the mean of the two estimates, which the agent acts on.
+pub fn double_q_learning(rules: TableRules, features: Features) -> Learner {
+    println!("Running in Double Q-Learning mode");
+    let mut shoe = Shoe::from(&rules);
+    shoe.set_counting(features.counting);
+    let mut tables = [QTable::new(0.0), QTable::new(0.0)];
+    Learner::new_trained(|q_table, insurance_table, bet_table, episode_number| {
+        let (result, error) = episode(&mut shoe, &rules, &features, q_table, insurance_table, episode_number, Mode::DoubleQ(&mut tables));
+        learn_bets(bet_table, &result.bet, result.round_reward());
+        (result.round_reward(), error)
+    })
+}
+
+/// returns which of the two estimates is updated in the episode
+pub fn updated_table(episode_number: usize) -> usize {
+    return if episode_number.is_multiple_of(2) { 0 } else { 1 };
+}
+
+/// returns the value of the next state for the estimate updated in the episode: its best action, valued with the other estimate
+pub fn double_q_target(tables: &[QTable<BlackjackState, BlackjackAction>; 2], agent_state: &BlackjackState, actions: &[BlackjackAction], episode_number: usize) -> f64 {
+    let updated = updated_table(episode_number);
+    let best_action = greedy_policy(agent_state, actions, &tables[updated], episode_number);
+    return tables[1 - updated].get_value(&StateAction { agent_state: *agent_state, action: best_action });
+}
+
+/// learns with Q-learning and with Double Q-learning, and prints how much each one overestimates the exact
+/// value of the states, along with how far their values are from the exact ones. Both learn the game the
+/// infinite deck values are exact for, so that the bias is not mixed up with the effect of removing the dealt cards.
+pub fn print_bias_report(rules: TableRules, features: Features) {
+    let game = InfiniteDeckSolver::closest_game(&rules);
+    let exact = InfiniteDeckSolver::new(&game).q_table();
+    let q_learning = sarsamax(game, features).exact_comparison(&exact);
+    let double_q = double_q_learning(game, features).exact_comparison(&exact);
+
+    println!("\nMaximization bias against the infinite deck values, learnt on {} continuously shuffled decks:", game.decks);
+    println!("mode              | mean bias | mean error | max error | best action agrees");
+    println!("------------------------------------------------------------------------------");
+    for (name, comparison) in [("Q-learning", q_learning), ("Double Q-learning", double_q)] {
+        println!("{:<17} | {:>+9.4} | {:>10.4} | {:>9.4} | {} of {} states", name, comparison.mean_bias, comparison.mean_error,
+                 comparison.max_error, comparison.agreeing, comparison.states);
+    }
+    println!("------------------------------------------------------------------------------");
+    //the values of both are also pulled down by the exploring of the early episodes, which the bias includes
+    println!("Double Q-learning values the states {:+.4} units lower on average", q_learning.mean_bias - double_q.mean_bias);
+    println!();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::insurance::{InsuranceAction, InsuranceState};
+    use super::*;
+
+    #[test]
+    fn test_tables_take_turns() {
+        assert_eq!(0, updated_table(0));
+        assert_eq!(1, updated_table(1));
+        assert_eq!(0, updated_table(2));
+        assert_eq!(1, updated_table(499999));
+    }
+
+    #[test]
+    fn test_target() {
+        let agent_state = BlackjackState { player: 16, dealer: 10, ace: false, pair: false, composition: None, count: None };
+        let hit = StateAction { agent_state, action: BlackjackAction::Hit };
+        let stand = StateAction { agent_state, action: BlackjackAction::Stand };
+        let mut tables = [QTable::new(0.0), QTable::new(0.0)];
+        tables[0].update_value(&hit, 0.5);
+        tables[0].update_value(&stand, 0.1);
+        tables[1].update_value(&hit, -0.3);
+        tables[1].update_value(&stand, 0.9);
+        let actions = [BlackjackAction::Hit, BlackjackAction::Stand];
+
+        //the first table picks the hit, which the second one values
+        assert_eq!(-0.3, double_q_target(&tables, &agent_state, &actions, 0));
+        //the second table picks the stand, which the first one values
+        assert_eq!(0.1, double_q_target(&tables, &agent_state, &actions, 1));
+        //a single action is taken whichever table is updated
+        assert_eq!(0.9, double_q_target(&tables, &agent_state, &actions[1..], 0));
+    }
+
+    #[test]
+    fn test_episodes() {
+        let rules = TableRules::default();
+        let features = Features::default();
+        let mut shoe = Shoe::from(&rules);
+        let mut q_table = QTable::new(0.0);
+        let mut insurance_table: QTable<InsuranceState, InsuranceAction> = QTable::new(0.0);
+        let mut tables = [QTable::new(0.0), QTable::new(0.0)];
+
+        //only the first table is updated in the even episodes
+        for episode_number in (0..200).step_by(2) {
+            episode(&mut shoe, &rules, &features, &mut q_table, &mut insurance_table, episode_number, Mode::DoubleQ(&mut tables));
+        }
+        assert!(!tables[0].get_all_values().is_empty());
+        assert!(tables[1].get_all_values().is_empty());
+
+        for episode_number in (1..200).step_by(2) {
+            episode(&mut shoe, &rules, &features, &mut q_table, &mut insurance_table, episode_number, Mode::DoubleQ(&mut tables));
+        }
+        assert!(!tables[1].get_all_values().is_empty());
+
+        //the agent acts on the mean of the two estimates
+        assert!(!q_table.get_all_values().is_empty());
+        for (state_action, value) in q_table.get_all_values() {
+            let mean = (tables[0].get_value(&state_action) + tables[1].get_value(&state_action)) / 2.0;
+            assert!((value - mean).abs() < 1e-9);
+        }
+    }
+}
@@ -0,0 +1,274 @@
+use crate::bankroll::{simulate, Bankroll, BettingStrategy, Player};
+use crate::basic_strategy::BasicStrategy;
+use crate::blackjack_agent::{round_reward, Count, Features};
+use crate::counting::SYSTEMS;
+use crate::learner::CountDeviations;
+use crate::rules::{Shuffling, TableRules};
+use crate::sarsa::sarsa;
+use crate::shoe::Shoe;
+
+/// How a player fared over many independent sessions of the same number of hands, each starting with the same bankroll.
+#[derive(Debug, Clone)]
+pub struct SessionReport {
+    pub sessions: usize,
+    /// how many hands each session was to play, unless the bankroll was lost first
+    pub hands: usize,
+    /// the bankroll each session started with
+    pub bankroll: f64,
+    /// how many sessions lost the whole bankroll
+    pub ruined: usize,
+    /// the bankroll each session ended with, from the lowest to the highest
+    pub balances: Vec<f64>,
+    /// the mean amount won per hand over all the sessions
+    pub mean_per_hand: f64,
+    /// the standard deviation of the amount won per hand over all the sessions
+    pub std_dev_per_hand: f64,
+}
+
+impl SessionReport {
+    /// returns the fraction of the sessions that lost the whole bankroll
+    pub fn risk_of_ruin(&self) -> f64 {
+        return self.ruined as f64 / usize::max(self.sessions, 1) as f64;
+    }
+
+    /// returns the bankroll that the specified percentage (from 0 to 100) of the sessions ended with or below
+    pub fn percentile(&self, percent: f64) -> f64 {
+        if self.balances.is_empty() {
+            return self.bankroll;
+        }
+        //the nearest rank
+        let rank = (percent / 100.0 * self.balances.len() as f64).ceil() as usize;
+        return self.balances[rank.clamp(1, self.balances.len()) - 1];
+    }
+
+    pub fn median(&self) -> f64 {
+        return self.percentile(50.0);
+    }
+
+    /// returns N0, how many hands it takes for the expected win to catch up with one standard deviation,
+    /// None when the player has no advantage
+    pub fn n0(&self) -> Option<f64> {
+        if self.mean_per_hand <= 0.0 {
+            return None;
+        }
+        let ratio = self.std_dev_per_hand / self.mean_per_hand;
+        return Some(ratio * ratio);
+    }
+
+    pub fn print(&self, strategy: &BettingStrategy) {
+        println!("\nSessions of {} hands with a bankroll of {:.2}, betting {:?}:", self.hands, self.bankroll, strategy);
+        println!("sessions:            {}", self.sessions);
+        println!("risk of ruin:        {:.2}%", 100.0 * self.risk_of_ruin());
+        println!("bankroll  5th pct:   {:.2}", self.percentile(5.0));
+        println!("         25th pct:   {:.2}", self.percentile(25.0));
+        println!("           median:   {:.2}", self.median());
+        println!("         75th pct:   {:.2}", self.percentile(75.0));
+        println!("         95th pct:   {:.2}", self.percentile(95.0));
+        println!("won per hand:        {:.4}", self.mean_per_hand);
+        println!("std dev per hand:    {:.4}", self.std_dev_per_hand);
+        match self.n0() {
+            Some(n0) => println!("N0:                  {:.0} hands", n0),
+            None => println!("N0:                  - (no advantage)")
+        }
+        println!();
+    }
+}
+
+/// plays the sessions independently, each from a freshly shuffled shoe, and reports how the bankroll fared
+pub fn evaluate_sessions(player: &dyn Player, rules: &TableRules, features: &Features, strategy: &BettingStrategy,
+                         bankroll: f64, hands: usize, sessions: usize) -> SessionReport {
+    let mut balances = Vec::with_capacity(sessions);
+    let mut ruined = 0;
+    //the sums of the amounts won per hand and of their squares
+    let mut played = 0;
+    let mut won = 0.0;
+    let mut won_squared = 0.0;
+
+    for _ in 0..sessions {
+        let session = simulate(player, rules, features, strategy, Bankroll::new(bankroll), hands);
+        for hand in session.trajectory.windows(2) {
+            let amount = hand[1] - hand[0];
+            won += amount;
+            won_squared += amount * amount;
+            played += 1;
+        }
+
+        if session.is_ruined() {
+            ruined += 1;
+        }
+        balances.push(session.balance);
+    }
+
+    balances.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let played = usize::max(played, 1) as f64;
+    let mean_per_hand = won / played;
+    let std_dev_per_hand = f64::sqrt(f64::max(won_squared / played - mean_per_hand * mean_per_hand, 0.0));
+
+    return SessionReport { sessions, hands, bankroll, ruined, balances, mean_per_hand, std_dev_per_hand };
+}
+
+/// How much more a player wins per hand than basic strategy at a flat bet of a unit, both being dealt the same hands.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Gain {
+    pub hands: usize,
+    /// how many hands the player came out of differently from basic strategy
+    pub differing: usize,
+    pub mean_per_hand: f64,
+    /// the standard error of the mean, of the difference between the two players on each hand
+    pub std_error: f64,
+}
+
+/// plays every hand twice from the same shoe, once with the player and once with basic strategy, and returns how much more
+/// the player wins per hand. As both play the same cards at the same count, the luck of the deal cancels out of the difference,
+/// which then takes far fewer hands to measure than comparing the two over separate shoes.
+pub fn gain_over_basic_strategy(player: &dyn Player, rules: &TableRules, features: &Features, hands: usize) -> Gain {
+    let mut shoe = Shoe::from(rules);
+    shoe.set_counting(features.counting);
+    let mut differing = 0;
+    let mut gained = 0.0;
+    let mut gained_squared = 0.0;
+
+    for _ in 0..hands {
+        shoe.shuffle_if_due();
+        //the player plays from a copy, and basic strategy moves the shoe on
+        let won = round_reward(&player.play_round(&mut shoe.clone(), rules, features));
+        let basic = round_reward(&BasicStrategy.play_round(&mut shoe, rules, features));
+        if won != basic {
+            differing += 1;
+        }
+        gained += won - basic;
+        gained_squared += (won - basic) * (won - basic);
+    }
+
+    let played = usize::max(hands, 1) as f64;
+    let mean_per_hand = gained / played;
+    let variance = f64::max(gained_squared / played - mean_per_hand * mean_per_hand, 0.0);
+    return Gain { hands, differing, mean_per_hand, std_error: f64::sqrt(variance / played) };
+}
+
+/// learns to play with the true count of each counting system, then plays basic strategy with the deviations learnt with each
+/// system and prints how much more it wins per hand than basic strategy, which is the playing efficiency of the system
+/// measured by simulation. A deviation is only made once both actions have been tried the specified number of times.
+pub fn print_playing_efficiency(rules: TableRules, hands: usize, visits: usize) {
+    let mut rows = Vec::new();
+    for system in SYSTEMS {
+        let features = Features { count: Count::True, counting: system, ..Features::default() };
+        let learner = sarsa(rules, features);
+        rows.push((system.name(), gain_over_basic_strategy(&CountDeviations { learner: &learner, visits }, &rules, &features, hands)));
+    }
+
+    println!("\nPlaying efficiency over {} hands dealt to both the deviations and basic strategy, {} decks:", hands, rules.decks);
+    println!("system      | gain per hand | std error | hands that differ");
+    println!("------------------------------------------------------------");
+    for (name, gain) in rows {
+        println!("{:<11} | {:>+13.5} | {:>9.5} | {}", name, gain.mean_per_hand, gain.std_error, gain.differing);
+    }
+    println!("------------------------------------------------------------");
+    println!();
+}
+
+/// learns with the running count on a shoe dealt down to the cut card and on a continuous shuffler, and prints what the count
+/// is worth to each: the counts the hands were played at, the plays learnt to deviate at a count, what playing basic strategy
+/// with those deviations gains over the same hands without them, and what the insurance decisions learnt at each count win.
+/// The continuous shuffler puts the cards back after every round, so the count only ever reflects the cards of the round.
+pub fn compare_shuffling(rules: TableRules, hands: usize, visits: usize) {
+    let features = Features { count: Count::Running, ..Features::default() };
+    let mut learners = Vec::new();
+    for (name, shuffling) in [("cut card", Shuffling::CutCard), ("continuous", Shuffling::Continuous)] {
+        let rules = TableRules { shuffling, ..rules };
+        let learner = sarsa(rules, features);
+        let gain = gain_over_basic_strategy(&CountDeviations { learner: &learner, visits }, &rules, &features, hands);
+        learners.push((name, learner, gain));
+    }
+
+    println!("\nValue of the running count, {} decks (deviations from states played at least {} times, over {} hands):", rules.decks, visits, hands);
+    println!("shoe       | counts seen | deviations | gain per hand | std error | insurance gain");
+    println!("---------------------------------------------------------------------------------");
+    for (name, learner, gain) in &learners {
+        let (lowest, highest) = learner.count_range().unwrap_or_default();
+        println!("{:<10} | {:>+4} to {:>+3} | {:>10} | {:>+13.5} | {:>9.5} | {:+.4}", name, lowest, highest, learner.count_divergences(visits).len(),
+                 gain.mean_per_hand, gain.std_error, learner.insurance_gain());
+    }
+    println!("---------------------------------------------------------------------------------");
+
+    println!("\nValue of taking insurance at each running count:");
+    println!("count | cut card | continuous");
+    println!("------------------------------");
+    let (shoe, continuous) = (&learners[0].1, &learners[1].1);
+    for count in -20..=20 {
+        let (shoe_value, continuous_value) = (shoe.insurance_value(count, false), continuous.insurance_value(count, false));
+        if shoe_value.is_none() && continuous_value.is_none() {
+            continue;
+        }
+        let label = |value: Option<f64>| value.map(|value| format!("{:+.3}", value)).unwrap_or("-".to_string());
+        println!("{:>+5} | {:<8} | {}", count, label(shoe_value), label(continuous_value));
+    }
+    println!("------------------------------");
+    println!();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(balances: Vec<f64>, mean_per_hand: f64, std_dev_per_hand: f64) -> SessionReport {
+        let ruined = balances.iter().filter(|balance| **balance <= 0.0).count();
+        return SessionReport { sessions: balances.len(), hands: 100, bankroll: 100.0, ruined, balances, mean_per_hand, std_dev_per_hand };
+    }
+
+    #[test]
+    fn test_report_statistics() {
+        let report = report(vec![0.0, 50.0, 90.0, 110.0, 150.0], 0.02, 1.1);
+
+        assert_eq!(0.2, report.risk_of_ruin());
+        assert_eq!(90.0, report.median());
+        assert_eq!(0.0, report.percentile(5.0));
+        assert_eq!(150.0, report.percentile(95.0));
+        assert_eq!(150.0, report.percentile(100.0));
+        assert!((report.n0().unwrap() - 3025.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_no_advantage_has_no_n0() {
+        assert_eq!(None, report(vec![90.0], -0.005, 1.15).n0());
+        assert_eq!(None, report(vec![90.0], 0.0, 1.15).n0());
+    }
+
+    #[test]
+    fn test_gain_over_itself() {
+        //dealt the same cards, basic strategy wins exactly what it wins, six decks never run out before the cut card
+        let rules = TableRules { decks: 6, ..TableRules::default() };
+        let gain = gain_over_basic_strategy(&BasicStrategy, &rules, &Features::default(), 1000);
+        assert_eq!(Gain { hands: 1000, differing: 0, mean_per_hand: 0.0, std_error: 0.0 }, gain);
+    }
+
+    #[test]
+    fn test_evaluate_basic_strategy() {
+        let rules = TableRules::default();
+        let report = evaluate_sessions(&BasicStrategy, &rules, &Features::default(), &BettingStrategy::Flat { bet: 1.0 },
+                                       1000.0, 400, 50);
+
+        assert_eq!(50, report.balances.len());
+        assert!(report.balances.windows(2).all(|pair| pair[0] <= pair[1]));
+        //a bankroll of 1000 bets is never lost in 400 hands, so every session played them all and the mean
+        //per hand is what the balances gained
+        assert_eq!(0, report.ruined);
+        let gained: f64 = report.balances.iter().map(|balance| balance - 1000.0).sum();
+        assert!((gained - report.mean_per_hand * 400.0 * 50.0).abs() < 1e-6);
+        //basic strategy is close to break even on a single deck, and a hand has a standard deviation of about 1.15 units,
+        //both well within 5 standard errors of 20000 hands
+        assert!(report.mean_per_hand.abs() < 0.04, "{}", report.mean_per_hand);
+        assert!((report.std_dev_per_hand - 1.15).abs() < 0.08, "{}", report.std_dev_per_hand);
+    }
+
+    #[test]
+    fn test_evaluate_ruin() {
+        //with a single bet in the bankroll nearly every session is lost well before 1000 hands
+        let report = evaluate_sessions(&BasicStrategy, &TableRules::default(), &Features::default(), &BettingStrategy::Flat { bet: 1.0 },
+                                       1.0, 1000, 20);
+
+        assert!(report.risk_of_ruin() >= 0.8, "{}", report.risk_of_ruin());
+        assert!(report.balances[..report.ruined].iter().all(|balance| *balance <= 0.0));
+        assert!(report.balances[report.ruined..].iter().all(|balance| *balance > 0.0));
+    }
+}
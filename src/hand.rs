@@ -1,11 +1,23 @@
 use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+use crate::card::Card;
 
 /// Represents the current hand, which includes the player sum and the dealer sum.
 /// The ace boolean variable represents whether an ace was used to count as 11.
-#[derive(Debug, Copy, Clone, Hash, Eq)]
+/// The cards variable holds the cards dealt to the hand in order.
+#[derive(Debug, Clone, Eq)]
 pub struct Hand {
     pub sum: u8,
     pub ace: bool,
+    pub cards: Vec<Card>,
+}
+
+impl Default for Hand {
+    fn default() -> Hand {
+        return Hand::new();
+    }
 }
 
 impl PartialEq for Hand {
@@ -14,6 +26,14 @@ impl PartialEq for Hand {
     }
 }
 
+impl Hash for Hand {
+    //hashes what the hands are compared on, so that equal hands dealt different cards hash the same
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.sum.hash(state);
+        self.ace.hash(state);
+    }
+}
+
 impl PartialOrd for Hand {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         return if self.is_bust() && other.is_bust() {
@@ -28,25 +48,82 @@ impl PartialOrd for Hand {
     }
 }
 
+impl fmt::Display for Hand {
+    /// shows the cards followed by the total, e.g. "A♠ 6♥ (soft 17)"
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for card in &self.cards {
+            write!(f, "{} ", card)?;
+        }
+        return write!(f, "({}{})", if self.ace { "soft " } else { "" }, self.sum);
+    }
+}
+
 impl Hand {
     pub fn new() -> Hand {
-        return Hand { sum: 0, ace: false };
+        return Hand { sum: 0, ace: false, cards: Vec::new() };
     }
 
-    pub fn from(c1: u8, c2: u8) -> Hand {
+    pub fn from(c1: Card, c2: Card) -> Hand {
         return Hand::new().hit(c1).hit(c2);
     }
 
-    pub fn hit(&self, card: u8) -> Hand {
-        let new_sum = self.sum + card;
-        return if card == 1 && new_sum + 10 <= 21 {
+    pub fn hit(&self, card: Card) -> Hand {
+        let new_sum = self.sum + card.value();
+        let mut cards = self.cards.clone();
+        cards.push(card);
+        return if card.is_ace() && new_sum + 10 <= 21 {
             //use the Ace card as an 11 instead of a 1
-            Hand { sum: new_sum + 10, ace: true }
+            Hand { sum: new_sum + 10, ace: true, cards }
         } else if new_sum > 21 && self.ace {
             //we busted using the Ace as 11, turn it back to a 1
-            Hand { sum: new_sum - 10, ace: false }
+            Hand { sum: new_sum - 10, ace: false, cards }
+        } else {
+            Hand { sum: new_sum, ace: self.ace, cards }
+        };
+    }
+
+    /// returns how many cards were dealt to the hand
+    pub fn card_count(&self) -> usize {
+        return self.cards.len();
+    }
+
+    /// returns the first card dealt to the hand
+    pub fn first(&self) -> Option<Card> {
+        return self.cards.first().copied();
+    }
+
+    /// returns true if the first card of the hand is an ace
+    pub fn first_ace(&self) -> bool {
+        return self.first().is_some_and(|first| first.is_ace());
+    }
+
+    /// returns true if an ace is counted as 11
+    pub fn is_soft(&self) -> bool {
+        return self.ace;
+    }
+
+    /// returns true if the hand is made of two cards of the same value
+    pub fn is_pair(&self) -> bool {
+        return self.card_count() == 2 && self.cards[0].value() == self.cards[1].value();
+    }
+
+    /// returns true if the hand is an ace and a ten valued card
+    pub fn is_natural(&self) -> bool {
+        return self.card_count() == 2 && self.sum == 21;
+    }
+
+    /// returns the total counting every ace as 1
+    pub fn hard_total(&self) -> u8 {
+        return self.cards.iter().map(|card| card.value()).sum();
+    }
+
+    /// returns the total counting an ace as 11, if the hand has an ace that can count as 11 without busting
+    pub fn soft_total(&self) -> Option<u8> {
+        let hard = self.hard_total();
+        return if self.cards.iter().any(|card| card.is_ace()) && hard + 10 <= 21 {
+            Some(hard + 10)
         } else {
-            Hand { sum: new_sum, ace: self.ace }
+            None
         };
     }
 
@@ -56,13 +133,17 @@ impl Hand {
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
+    use crate::card::{Rank, Suit};
     use crate::deck::Deck;
+    use crate::round::RoundState;
+    use crate::rules::TableRules;
     use super::*;
 
     #[test]
     fn test_hand_with_ace_1st() {
-        let hand = Hand::from(1, 2);
+        let hand = Hand::from(card(1), card(2));
 
         assert_eq!(hand.ace, true);
         assert_eq!(hand.sum, 13);
@@ -70,7 +151,7 @@ mod tests {
 
     #[test]
     fn test_hand_with_ace_2nd() {
-        let hand = Hand::from(5, 1);
+        let hand = Hand::from(card(5), card(1));
 
         assert_eq!(hand.ace, true);
         assert_eq!(hand.sum, 16);
@@ -78,7 +159,7 @@ mod tests {
 
     #[test]
     fn test_hand_with_two_aces() {
-        let hand = Hand::from(1, 1);
+        let hand = Hand::from(card(1), card(1));
 
         assert_eq!(hand.ace, true);
         assert_eq!(hand.sum, 12);
@@ -86,7 +167,7 @@ mod tests {
 
     #[test]
     fn test_hand_with_no_aces() {
-        let hand = Hand::from(5, 6);
+        let hand = Hand::from(card(5), card(6));
 
         assert_eq!(hand.ace, false);
         assert_eq!(hand.sum, 11);
@@ -94,7 +175,7 @@ mod tests {
 
     #[test]
     fn test_hand_with_no_aces_hits_ace() {
-        let hand = Hand::from(3, 6).hit(1);
+        let hand = Hand::from(card(3), card(6)).hit(card(1));
 
         assert_eq!(hand.ace, true);
         assert_eq!(hand.sum, 20);
@@ -102,18 +183,28 @@ mod tests {
 
     #[test]
     fn test_hand_with_no_aces_hits_ace_too_high() {
-        let hand = Hand::from(5, 6).hit(1);
+        let hand = Hand::from(card(5), card(6)).hit(card(1));
 
         assert_eq!(hand.ace, false);
         assert_eq!(hand.sum, 12);
     }
 
+    #[test]
+    fn test_pairs() {
+        assert!(Hand::from(card(1), card(1)).is_pair());
+        assert!(Hand::from(card(8), card(8)).is_pair());
+        assert!(Hand::from(card(10), card(10)).is_pair());
+        assert!(!Hand::from(card(10), card(6)).is_pair());
+        assert!(!Hand::from(card(1), card(5)).is_pair());
+        assert!(!Hand::from(card(4), card(4)).hit(card(2)).is_pair());
+    }
+
     #[test]
     fn test_busted_hand() {
-        let hand = Hand::from(10, 6);
+        let hand = Hand::from(card(10), card(6));
         assert_eq!(hand.sum, 16);
 
-        let hit_hand = hand.hit(10);
+        let hit_hand = hand.hit(card(10));
 
         assert!(hit_hand.is_bust());
         assert_eq!(hit_hand.sum, 26);
@@ -121,10 +212,10 @@ mod tests {
 
     #[test]
     fn test_not_busted_hand() {
-        let hand = Hand::from(10, 6);
+        let hand = Hand::from(card(10), card(6));
         assert_eq!(hand.sum, 16);
 
-        let hit_hand = hand.hit(2);
+        let hit_hand = hand.hit(card(2));
 
         assert!(!hit_hand.is_bust());
         assert_eq!(hit_hand.sum, 18);
@@ -132,21 +223,21 @@ mod tests {
 
     #[test]
     fn test_blackjack_with_ace_1st() {
-        let hand = Hand::from(1, 10);
+        let hand = Hand::from(card(1), card(10));
         assert_eq!(hand.sum, 21);
         assert!(!hand.is_bust());
     }
 
     #[test]
     fn test_blackjack_with_ace_2nd() {
-        let hand = Hand::from(10, 1);
+        let hand = Hand::from(card(10), card(1));
         assert_eq!(hand.sum, 21);
         assert!(!hand.is_bust());
     }
 
     #[test]
     fn test_blackjack_no_ace() {
-        let hand = Hand::from(10, 5).hit(6);
+        let hand = Hand::from(card(10), card(5)).hit(card(6));
 
         assert_eq!(hand.sum, 21);
         assert!(!hand.is_bust());
@@ -154,11 +245,11 @@ mod tests {
 
     #[test]
     fn test_winning_hand() {
-        let hand1 = Hand::from(10, 5).hit(6);
+        let hand1 = Hand::from(card(10), card(5)).hit(card(6));
         assert_eq!(hand1.sum, 21);
         assert!(!hand1.is_bust());
 
-        let hand2 = Hand::from(5, 7).hit(8);
+        let hand2 = Hand::from(card(5), card(7)).hit(card(8));
         assert_eq!(hand2.sum, 20);
         assert!(!hand2.is_bust());
 
@@ -169,11 +260,11 @@ mod tests {
 
     #[test]
     fn test_winning_against_bustedhand() {
-        let hand1 = Hand::from(10, 5).hit(8);
+        let hand1 = Hand::from(card(10), card(5)).hit(card(8));
         assert_eq!(hand1.sum, 23);
         assert!(hand1.is_bust());
 
-        let hand2 = Hand::from(5, 7).hit(8);
+        let hand2 = Hand::from(card(5), card(7)).hit(card(8));
         assert_eq!(hand2.sum, 20);
         assert!(!hand2.is_bust());
 
@@ -191,12 +282,74 @@ mod tests {
         let card2 = deck.deal().unwrap();
         let hand = Hand::from(card1, card2);
 
-        if (card1 == 1) || (card2 == 1) {
+        if card1.is_ace() || card2.is_ace() {
             assert_eq!(hand.ace, true);
-            assert_eq!(hand.sum, card1 + card2 + 10);
+            assert_eq!(hand.sum, card1.value() + card2.value() + 10);
         } else {
             assert_eq!(hand.ace, false);
-            assert_eq!(hand.sum, card1 + card2);
+            assert_eq!(hand.sum, card1.value() + card2.value());
         }
     }
+
+    #[test]
+    fn test_pair_of_ten_valued_cards() {
+        let hand = Hand::from(Card::new(Rank::King, Suit::Hearts), Card::new(Rank::Ten, Suit::Clubs));
+
+        assert!(hand.is_pair());
+        assert_eq!(Some(Card::new(Rank::King, Suit::Hearts)), hand.first());
+
+        //once split, each hand keeps its own card
+        let mut deck = Deck::new_rigged_cards(&[Card::new(Rank::King, Suit::Hearts), Card::new(Rank::Ten, Suit::Clubs), card(9), card(8), card(5)]);
+        let split = RoundState::new(&mut deck, &TableRules::default()).unwrap().split(&mut deck).unwrap();
+        assert_eq!(vec![Card::new(Rank::King, Suit::Hearts), card(5)], split.player.cards);
+        assert_eq!(vec![Card::new(Rank::Ten, Suit::Clubs)], split.waiting[0].cards);
+    }
+
+    #[test]
+    fn test_hands_remember_their_cards() {
+        let aces = Hand::from(card(1), card(1));
+        let five_seven = Hand::from(card(5), card(7));
+
+        assert_eq!(aces.sum, five_seven.sum);
+        assert!(aces.is_pair());
+        assert!(!five_seven.is_pair());
+        assert_eq!(vec![card(5), card(7)], five_seven.cards);
+        assert_eq!(2, aces.card_count());
+        assert_eq!(3, five_seven.hit(card(2)).card_count());
+    }
+
+    #[test]
+    fn test_display() {
+        let hand = Hand::from(Card::new(Rank::Ace, Suit::Spades), Card::new(Rank::Six, Suit::Hearts));
+        assert_eq!("A♠ 6♥ (soft 17)", hand.to_string());
+        assert_eq!("A♠ 6♥ K♣ (17)", hand.hit(Card::new(Rank::King, Suit::Clubs)).to_string());
+    }
+
+    #[test]
+    fn test_naturals() {
+        assert!(Hand::from(card(1), Card::new(Rank::Jack, Suit::Clubs)).is_natural());
+        assert!(!Hand::from(card(10), card(5)).hit(card(6)).is_natural());
+        assert!(!Hand::from(card(10), card(9)).is_natural());
+    }
+
+    #[test]
+    fn test_soft_and_hard_totals() {
+        let soft = Hand::from(card(1), card(6));
+        assert!(soft.is_soft());
+        assert_eq!(7, soft.hard_total());
+        assert_eq!(Some(17), soft.soft_total());
+
+        let hard = soft.hit(card(9));
+        assert!(!hard.is_soft());
+        assert_eq!(16, hard.hard_total());
+        assert_eq!(None, hard.soft_total());
+
+        let no_ace = Hand::from(card(10), card(6));
+        assert_eq!(16, no_ace.hard_total());
+        assert_eq!(None, no_ace.soft_total());
+    }
+
+    fn card(value: u8) -> Card {
+        return Card::from_value(value);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,66 @@
+use crate::blackjack_agent::{Count, EpisodeResult, Features};
+use crate::blackjack_policy::e_greedy_policy;
+use crate::deck::Deck;
+use crate::qtable::{Action, QTable, State, StateAction};
+use crate::round::{Insurance, RoundState};
+
+/// The state of the insurance decision, made before the player acts when the dealer shows an ace.
+/// The count tells how rich in tens the rest of the deck is.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub struct InsuranceState {
+    /// the true count when learning with true counts, the running count otherwise
+    pub count: i32,
+    /// the player has a natural, so insurance means taking even money
+    pub natural: bool,
+}
+
+impl State for InsuranceState {}
+
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum InsuranceAction {
+    Take,
+    Decline,
+}
+
+impl Action for InsuranceAction {}
+
+impl InsuranceState {
+    pub fn from(round_state: &RoundState, features: &Features) -> InsuranceState {
+        let count = match features.count {
+            Count::True => Count::True.of(round_state, features.rounding).unwrap(),
+            _ => round_state.count
+        };
+        return InsuranceState { count, natural: round_state.player.is_natural() };
+    }
+}
+
+/// decides on insurance if it is offered, returning the decision made so that it can be learnt at the end of the round
+pub fn insurance_episode(round_state: RoundState, deck: &mut Deck, features: &Features, insurance_table: &QTable<InsuranceState, InsuranceAction>, episode_number: usize)
+                         -> (RoundState, Option<StateAction<InsuranceState, InsuranceAction>>) {
+    if round_state.insurance != Insurance::Offered {
+        return (round_state, None);
+    }
+
+    let agent_state = InsuranceState::from(&round_state, features);
+    let action = e_greedy_policy(&agent_state, &[InsuranceAction::Take, InsuranceAction::Decline], insurance_table, episode_number);
+    let round_state = round_state.insure(action == InsuranceAction::Take, deck).unwrap();
+    return (round_state, Some(StateAction { agent_state, action }));
+}
+
+/// learns the insurance decision of the episode from what the insurance bet returned, if insurance was offered.
+/// It is learnt the same way whatever the method the playing decisions are learnt with.
+pub fn learn_episode_insurance(insurance_table: &mut QTable<InsuranceState, InsuranceAction>, result: &EpisodeResult) {
+    if let Some(insurance) = &result.insurance {
+        learn_insurance(insurance_table, insurance, result.insurance_reward);
+    }
+}
+
+/// updates the value of the insurance decision with the sample average of what the insurance bet returned,
+/// declining always returns 0 since the rest of the round is played the same way
+pub fn learn_insurance(insurance_table: &mut QTable<InsuranceState, InsuranceAction>, state_action: &StateAction<InsuranceState, InsuranceAction>, g: f64) {
+    let old_value = insurance_table.get_value(state_action);
+    let count = insurance_table.get_count(state_action) + 1;
+
+    let new_value = old_value + (g - old_value) / count as f64;
+    insurance_table.update_value(state_action, new_value);
+}
@@ -1,52 +1,95 @@
+use std::collections::HashMap;
+
 use crate::{BlackjackAction, BlackjackState, QTable};
+use crate::bankroll::{simulate, Bankroll, BettingStrategy, Player};
+use crate::basic_strategy::BasicStrategy;
+use crate::betting::{best_bet, BetAction, BetRamp, BetState, MAX_BET};
+use crate::blackjack_agent::{act, available_actions, composition_label, Features};
+use crate::blackjack_policy::greedy_policy;
+use crate::composition::{count_divergences, divergences, total_policy, Divergence};
+use crate::insurance::{InsuranceAction, InsuranceState};
+use crate::qtable::StateAction;
+use crate::round::{Insurance, RoundState};
+use crate::rules::TableRules;
+use crate::shoe::Shoe;
+use crate::solver::{compare, Comparison};
+
+/// How the learner was doing at a point of its training.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct LearningPoint {
+    pub episode: usize,
+    /// the fraction of the last 1000 episodes that were won
+    pub win_rate: f64,
+    /// the mean absolute error of the updates of all the episodes so far
+    pub error: f64,
+}
 
 pub struct Learner {
     q_table: QTable<BlackjackState, BlackjackAction>,
+    insurance_table: QTable<InsuranceState, InsuranceAction>,
+    bet_table: QTable<BetState, BetAction>,
+    /// how many episodes the learner was trained for
+    episodes: usize,
+    /// the win rate and the error every 1000 episodes of the training
+    curve: Vec<LearningPoint>,
+}
+
+impl Default for Learner {
+    fn default() -> Learner {
+        return Learner::new();
+    }
 }
 
 impl Learner {
     pub fn new() -> Learner {
-        Learner { q_table: QTable::new(0.0) }
+        Learner { q_table: QTable::new(0.0), insurance_table: QTable::new(0.0), bet_table: QTable::new(0.0), episodes: 0, curve: Vec::new() }
     }
 
     pub fn new_trained<F>(run_episode: F) -> Learner
-        where F: Fn(&mut QTable<BlackjackState, BlackjackAction>, usize) -> (i32, f64) {
+        where F: FnMut(&mut QTable<BlackjackState, BlackjackAction>, &mut QTable<InsuranceState, InsuranceAction>, &mut QTable<BetState, BetAction>, usize) -> (f64, f64) {
         let mut learner = Learner::new();
         learner.train(run_episode);
         return learner;
     }
 
-    pub fn train<F>(&mut self, run_episode: F)
-        where F: Fn(&mut QTable<BlackjackState, BlackjackAction>, usize) -> (i32, f64) {
+    pub fn train<F>(&mut self, mut run_episode: F)
+        where F: FnMut(&mut QTable<BlackjackState, BlackjackAction>, &mut QTable<InsuranceState, InsuranceAction>, &mut QTable<BetState, BetAction>, usize) -> (f64, f64) {
         let episodes = 500000;
 
         let mut wins = 0;
         let mut losses = 0;
         let mut draws = 0;
+        //net units won over the last 1000 episodes, so that rules paying fractions of a unit can be compared
+        let mut returns = 0.0;
         let mut avg_error = 0.0;
         let mut count = 0.0;
         for i in 0..episodes {
             if i % 1000 == 0 && i > 0 {
-                println!("\"{:?}\",\"{:?}\",\"{:?}\",\"{:?}\",\"{:?}\"", i, wins, losses, draws, avg_error);
+                println!("\"{:?}\",\"{:?}\",\"{:?}\",\"{:?}\",\"{:?}\",\"{:?}\"", i, wins, losses, draws, returns, avg_error);
+                self.record(i, wins, losses + draws, avg_error);
                 wins = 0;
                 losses = 0;
                 draws = 0;
+                returns = 0.0;
             }
 
-            count = count + 1.0;
-            let (reward, error) = run_episode(&mut self.q_table, i);
-            if reward > 0 {
+            count += 1.0;
+            self.episodes += 1;
+            let (reward, error) = run_episode(&mut self.q_table, &mut self.insurance_table, &mut self.bet_table, i);
+            if reward > 0.0 {
                 wins += 1;
-            } else if reward < 0 {
+            } else if reward < 0.0 {
                 losses += 1;
             } else {
                 draws += 1;
             }
+            returns += reward;
 
-            avg_error = avg_error + (error - avg_error) / count;
+            avg_error += (error - avg_error) / count;
         }
 
-        println!("\"{:?}\",\"{:?}\",\"{:?}\",\"{:?}\",\"{:?}\"", episodes - 1, wins, losses, draws, avg_error);
+        println!("\"{:?}\",\"{:?}\",\"{:?}\",\"{:?}\",\"{:?}\",\"{:?}\"", episodes - 1, wins, losses, draws, returns, avg_error);
+        self.record(episodes - 1, wins, losses + draws, avg_error);
 
         let q_values = self.q_table.get_all_values();
         println!("Total state action values: {:?}", q_values.len());
@@ -55,39 +98,345 @@ impl Learner {
         }
     }
 
+    /// returns the win rate and the error every 1000 episodes of the training
+    pub fn curve(&self) -> &[LearningPoint] {
+        return &self.curve;
+    }
+
+    fn record(&mut self, episode: usize, wins: usize, others: usize, error: f64) {
+        let win_rate = wins as f64 / usize::max(wins + others, 1) as f64;
+        self.curve.push(LearningPoint { episode, win_rate, error });
+    }
+
+    /// plays the learnt strategy for real money, betting on each round with the strategy,
+    /// until the rounds have been played or the bankroll is lost
+    pub fn simulate(&self, rules: &TableRules, features: &Features, strategy: &BettingStrategy, bankroll: Bankroll, rounds: usize) -> Bankroll {
+        return simulate(self, rules, features, strategy, bankroll, rounds);
+    }
+
+    /// prints the basic strategy, when learnt with compositions or counts their values are averaged for each hand total
     pub fn print_strategy(&self) {
-        self.print_strategy_ace(false);
-        self.print_strategy_ace(true);
+        let policy = self.q_table.get_aggregate_policy(|state| state.basic());
+        Learner::print_strategy_ace(&policy, false);
+        Learner::print_strategy_ace(&policy, true);
+        Learner::print_strategy_pairs(&policy);
+        self.print_insurance_strategy();
+    }
+
+    /// prints how far the learnt values of the total-dependent states are from the exact ones,
+    /// and how often the best learnt action is the best one. The exact values have to be for the game
+    /// that was learnt, such as the infinite deck ones for the closest game of the solver.
+    pub fn print_exact_comparison(&self, exact: &QTable<BlackjackState, BlackjackAction>) {
+        let comparison = self.exact_comparison(exact);
+
+        println!("\nLearnt against exact values:");
+        println!("state actions compared: {}", comparison.compared);
+        println!("mean absolute error:    {:.4}", comparison.mean_error);
+        println!("max absolute error:     {:.4}", comparison.max_error);
+        println!("best action agrees:     {} of {} states", comparison.agreeing, comparison.states);
+        println!("mean bias of the best:  {:+.4}", comparison.mean_bias);
+        println!();
+    }
+
+    /// returns how far the learnt values of the total-dependent states are from the exact ones
+    pub fn exact_comparison(&self, exact: &QTable<BlackjackState, BlackjackAction>) -> Comparison {
+        return compare(&self.q_table.aggregate(|state| state.basic()), exact);
+    }
+
+    /// returns how many units the bet agent learnt to bet at each true count it played at
+    pub fn bet_ramp(&self) -> BetRamp {
+        return BetRamp { units: self.bet_table.get_states().iter().map(|state| (state.count, best_bet(&self.bet_table, state).units)).collect() };
+    }
+
+    /// prints how many units the bet agent learnt to bet at each true count, along with the value of the smallest
+    /// and the largest bets, in growth of the log of the bankroll per round
+    pub fn print_bet_ramp(&self) {
+        let mut states = self.bet_table.get_states();
+        states.sort_by_key(|state| state.count);
+
+        println!("\nBet ramp:");
+        println!("count | bet | rounds  | value of 1 unit | value of {} units", MAX_BET);
+        println!("-------------------------------------------------------------");
+        for state in states {
+            let value = |units| self.bet_table.get_value(&StateAction { agent_state: state, action: BetAction { units } });
+            let rounds = self.bet_table.get_count(&StateAction { agent_state: state, action: BetAction { units: 1 } });
+            println!("{:>+5} | {:>3} | {:>7} | {:>15.7} | {:>.7}", state.count, best_bet(&self.bet_table, &state).units, rounds, value(1), value(MAX_BET));
+        }
+        println!("-------------------------------------------------------------");
+        println!();
+    }
+
+    /// prints the hands where playing the cards rather than the total makes a difference, if learnt with compositions,
+    /// along with what this is worth per round
+    pub fn print_composition_report(&self) {
+        //values of states played only a few times are too noisy to compare
+        let min_visits = 1000;
+        let divergences = divergences(&self.q_table, min_visits);
+
+        println!("\nComposition-dependent plays (states played at least {} times):", min_visits);
+        println!("cards        | dealer | action    | total action | gain   | visits");
+        println!("-------------------------------------------------------------------");
+        let mut edge = 0.0;
+        for divergence in &divergences {
+            let cards = composition_label(&divergence.state.composition.unwrap());
+            let pair = if divergence.state.pair { " pair" } else { "" };
+            println!("{:<12} | {:>6} | {:<9} | {:<12} | {:.3}  | {}", format!("{}{}", cards, pair), divergence.state.dealer,
+                     format!("{:?}", divergence.action), format!("{:?}", divergence.total_action), divergence.gain, divergence.visits);
+            edge += divergence.gain * divergence.visits as f64;
+        }
+        println!("-------------------------------------------------------------------");
+        let rounds = usize::max(self.episodes, 1);
+        println!("{} diverging states, worth {:.5} units per round", divergences.len(), edge / rounds as f64);
+        println!();
+    }
+
+    /// prints the action learnt at each true count for the Illustrious 18 index plays, if learnt with true counts,
+    /// next to the index from which the play deviates from basic strategy
+    pub fn print_count_strategy(&self) {
+        //player total, soft, pair, dealer up card, the deviation and the index it is made at
+        let plays: [(u8, bool, bool, u8, &str); 17] = [
+            (16, false, false, 10, "S >= 0"), (15, false, false, 10, "S >= +4"), (20, false, true, 5, "P >= +5"),
+            (20, false, true, 6, "P >= +4"), (10, false, false, 10, "D >= +4"), (12, false, false, 3, "S >= +2"),
+            (12, false, false, 2, "S >= +3"), (11, false, false, 11, "D >= +1"), (9, false, false, 2, "D >= +1"),
+            (10, false, false, 11, "D >= +4"), (9, false, false, 7, "D >= +3"), (16, false, false, 9, "S >= +5"),
+            (13, false, false, 2, "H <= -2"), (12, false, false, 4, "H <= -1"), (12, false, false, 5, "H <= -3"),
+            (12, false, false, 6, "H <= -2"), (13, false, false, 3, "H <= -3"),
+        ];
+        let policy = total_policy(&self.q_table);
+        //the indices all lie in this range
+        let lowest = -4;
+        let highest = 6;
+
+        println!("\nIllustrious 18 (insurance is in the insurance table):");
+        print!("hand      | index   |");
+        for count in lowest..=highest {
+            print!(" {:>+2} |", count);
+        }
+        println!();
+        println!("--------------------------------------------------------------------------");
+        for (player, ace, pair, dealer, index) in plays {
+            let dealer_label = if dealer == 11 { "A".to_string() } else { dealer.to_string() };
+            let hand = if pair { format!("{},{}", player / 2, player / 2) } else { player.to_string() };
+            print!("{:<9} | {:<7} |", format!("{} v {}", hand, dealer_label), index);
+            for count in lowest..=highest {
+                let state = BlackjackState { player, dealer, ace, pair, composition: None, count: Some(count) };
+                print!("  {} |", Learner::action_letter(policy.get(&state)));
+            }
+            println!();
+        }
+        println!("--------------------------------------------------------------------------");
+        println!();
+    }
+
+    /// prints whether insurance (or even money with a natural) is taken for each count seen
+    fn print_insurance_strategy(&self) {
+        let policy = self.insurance_table.get_policy();
+        let mut states: Vec<&InsuranceState> = policy.keys().collect();
+        states.sort_by_key(|state| (state.natural, state.count));
+
+        println!("\nInsurance:");
+        println!("count | natural | action  | value of taking");
+        println!("---------------------------------------------");
+        for state in states {
+            let take = self.insurance_table.get_value(&StateAction { agent_state: *state, action: InsuranceAction::Take });
+            println!("{:>5} | {:>7} | {:<7} | {:.3}", state.count, state.natural, format!("{:?}", policy[state]), take);
+        }
+        println!("---------------------------------------------");
+        println!();
+    }
+
+    /// returns the states played at a count at least min_visits times, where the best action learnt at the count
+    /// differs from the best one for the hand whatever the count
+    pub fn count_divergences(&self, min_visits: usize) -> Vec<Divergence> {
+        return count_divergences(&self.q_table, min_visits);
+    }
+
+    /// returns the lowest and the highest count the hands were played at, None if learnt without the count
+    pub fn count_range(&self) -> Option<(i32, i32)> {
+        let counts: Vec<i32> = self.q_table.get_states().iter().filter_map(|state| state.count).collect();
+        return counts.iter().min().zip(counts.iter().max()).map(|(lowest, highest)| (*lowest, *highest));
     }
 
-    fn print_strategy_ace(&self, ace: bool) {
-        let policy = self.q_table.get_policy();
+    /// returns the learnt value of taking insurance (or even money with a natural) at the count, None if it was never offered at it
+    pub fn insurance_value(&self, count: i32, natural: bool) -> Option<f64> {
+        let agent_state = InsuranceState { count, natural };
+        let take = StateAction { agent_state, action: InsuranceAction::Take };
+        let decline = StateAction { agent_state, action: InsuranceAction::Decline };
+        if self.insurance_table.get_count(&take) + self.insurance_table.get_count(&decline) == 0 {
+            return None;
+        }
+        return Some(self.insurance_table.get_value(&take));
+    }
 
+    /// returns what the learnt insurance decisions win per insurance offered, insurance being taken only at the counts
+    /// its value is positive at, which is what counting is worth to the insurance decision
+    pub fn insurance_gain(&self) -> f64 {
+        let mut offered = 0;
+        let mut won = 0.0;
+        for agent_state in self.insurance_table.get_states() {
+            let take = StateAction { agent_state, action: InsuranceAction::Take };
+            let decline = StateAction { agent_state, action: InsuranceAction::Decline };
+            let visits = self.insurance_table.get_count(&take) + self.insurance_table.get_count(&decline);
+            offered += visits;
+            won += visits as f64 * f64::max(self.insurance_table.get_value(&take), 0.0);
+        }
+        return won / usize::max(offered, 1) as f64;
+    }
+
+    fn print_strategy_ace(policy: &HashMap<BlackjackState, BlackjackAction>, ace: bool) {
         println!("\nAce: {:?}", ace);
+        Learner::print_header();
+        //soft hands start at 12 (a pair of aces), hard hands that can be doubled start much lower
+        let lowest = if ace { 12u8 } else { 5u8 };
+        for player in (lowest..=20).rev() {
+            Learner::print_row(&format!("{:>2}", player), policy, |dealer| BlackjackState { player, dealer, ace, pair: false, composition: None, count: None });
+        }
+        Learner::print_footer();
+    }
+
+    fn print_strategy_pairs(policy: &HashMap<BlackjackState, BlackjackAction>) {
+        println!("\nPairs:");
+        Learner::print_header();
+        for card in [1u8, 10, 9, 8, 7, 6, 5, 4, 3, 2] {
+            let label = if card == 1 { "AA".to_string() } else { format!("{:>2}", card) };
+            let (player, ace) = if card == 1 { (12, true) } else { (2 * card, false) };
+            Learner::print_row(&label, policy, |dealer| BlackjackState { player, dealer, ace, pair: true, composition: None, count: None });
+        }
+        Learner::print_footer();
+    }
+
+    fn print_header() {
         print!("   |");
         for header in 2u8..=10 {
             print!(" {} |", header);
         }
         println!(" A |");
         println!("---------------------------------------------");
-        for player in (12u8 ..=20).rev() {
-            print!("{} |", player);
-            for dealer in 2u8..=11 {
-                let state = BlackjackState { player, dealer, ace };
-
-                if dealer == 10 {
-                    print!(" ");
-                }
-                match policy.get(&state) {
-                    None => print!(" - |"),
-                    Some(BlackjackAction::Hit) => print!(" H |"),
-                    Some(BlackjackAction::Stand) =>  print!(" S |")
-                }
+    }
+
+    fn print_footer() {
+        println!("---------------------------------------------");
+        println!();
+    }
+
+    fn print_row<F>(label: &str, policy: &HashMap<BlackjackState, BlackjackAction>, state_for_dealer: F)
+        where F: Fn(u8) -> BlackjackState {
+        print!("{} |", label);
+        for dealer in 2u8..=11 {
+            let state = state_for_dealer(dealer);
+
+            if dealer == 10 {
+                print!(" ");
             }
-            println!();
+            print!(" {} |", Learner::action_letter(policy.get(&state)));
         }
-        println!("---------------------------------------------");
         println!();
     }
+
+    fn action_letter(action: Option<&BlackjackAction>) -> &'static str {
+        return match action {
+            None => "-",
+            Some(BlackjackAction::Hit) => "H",
+            Some(BlackjackAction::Stand) => "S",
+            Some(BlackjackAction::Double) => "D",
+            Some(BlackjackAction::Split) => "P",
+            Some(BlackjackAction::Surrender) => "R"
+        };
+    }
+}
+
+/// prints the learning curves of the named learners side by side, as comma separated values
+pub fn print_learning_curves(learners: &[(&str, &Learner)]) {
+    let mut header = vec!["\"episode\"".to_string()];
+    for (name, _) in learners {
+        header.push(format!("\"{} win rate\",\"{} error\"", name, name));
+    }
+    println!("\nLearning curves:");
+    println!("{}", header.join(","));
+
+    let points = learners.iter().map(|(_, learner)| learner.curve.len()).min().unwrap_or_default();
+    for i in 0..points {
+        let mut row = vec![format!("\"{:?}\"", learners[0].1.curve[i].episode)];
+        for (_, learner) in learners {
+            row.push(format!("\"{:.4}\",\"{:.6}\"", learner.curve[i].win_rate, learner.curve[i].error));
+        }
+        println!("{}", row.join(","));
+    }
+    println!();
+}
+
+impl Player for Learner {
+    /// plays a round from the shoe greedily with the learnt strategy, taking insurance if it was learnt to be worth it
+    fn play_round(&self, shoe: &mut Shoe, rules: &TableRules, features: &Features) -> RoundState {
+        let mut round_state = shoe.new_round(rules).unwrap();
+
+        if round_state.insurance == Insurance::Offered {
+            let agent_state = InsuranceState::from(&round_state, features);
+            let action = greedy_policy(&agent_state, &[InsuranceAction::Take, InsuranceAction::Decline], &self.insurance_table, 0);
+            round_state = round_state.insure(action == InsuranceAction::Take, shoe.deck()).unwrap();
+        }
+
+        while !round_state.finished() {
+            let agent_state = BlackjackState::from_features(&round_state, features);
+            let action = greedy_policy(&agent_state, &available_actions(&round_state), &self.q_table, 0);
+            round_state = act(&round_state, action, shoe.deck()).unwrap();
+        }
+
+        shoe.end_round(&round_state);
+        return round_state;
+    }
+}
+
+/// Basic strategy that deviates to the action the learner values best at the count, only where the learner tried both
+/// that action and the basic strategy one often enough for their values to be told apart. It also takes insurance where
+/// the learner found it worth taking. Played with a flat bet, it is what the count the learner was trained with is worth to the play.
+pub struct CountDeviations<'a> {
+    pub learner: &'a Learner,
+    /// how many times an action must have been tried at the count to deviate to it
+    pub visits: usize,
 }
 
+impl CountDeviations<'_> {
+    /// returns the action for the current hand, the basic strategy one unless another was learnt to be better at the count
+    pub fn action(&self, round_state: &RoundState, features: &Features) -> BlackjackAction {
+        let basic = BasicStrategy::action(round_state);
+        let agent_state = BlackjackState::from_features(round_state, features);
+        let value = |action: BlackjackAction| {
+            let state_action = StateAction { agent_state, action };
+            return if self.learner.q_table.get_count(&state_action) >= self.visits { Some(self.learner.q_table.get_value(&state_action)) } else { None };
+        };
+        let Some(basic_value) = value(basic) else {
+            return basic;
+        };
+
+        return available_actions(round_state).into_iter()
+            .filter_map(|action| value(action).map(|action_value| (action, action_value)))
+            .filter(|(_, action_value)| *action_value > basic_value)
+            .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+            .map(|(action, _)| action)
+            .unwrap_or(basic);
+    }
+
+    /// returns true if taking insurance was tried often enough at the count and learnt to win
+    fn insures(&self, agent_state: InsuranceState) -> bool {
+        let take = StateAction { agent_state, action: InsuranceAction::Take };
+        return self.learner.insurance_table.get_count(&take) >= self.visits && self.learner.insurance_table.get_value(&take) > 0.0;
+    }
+}
+
+impl Player for CountDeviations<'_> {
+    fn play_round(&self, shoe: &mut Shoe, rules: &TableRules, features: &Features) -> RoundState {
+        let mut round_state = shoe.new_round(rules).unwrap();
+        if round_state.insurance == Insurance::Offered {
+            let insures = self.insures(InsuranceState::from(&round_state, features));
+            round_state = round_state.insure(insures, shoe.deck()).unwrap();
+        }
+
+        while !round_state.finished() {
+            round_state = act(&round_state, self.action(&round_state, features), shoe.deck()).unwrap();
+        }
+
+        shoe.end_round(&round_state);
+        return round_state;
+    }
+}
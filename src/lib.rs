@@ -1,8 +1,12 @@
+#![allow(clippy::needless_return)]
+
 use crate::blackjack_agent::{BlackjackAction, BlackjackState};
 use crate::qtable::{QTable};
 
 pub mod round;
+pub mod card;
 pub mod deck;
+pub mod shoe;
 pub mod hand;
 pub mod monte_carlo;
 pub mod qtable;
@@ -10,5 +14,15 @@ pub mod sarsa;
 pub mod blackjack_agent;
 pub mod blackjack_policy;
 pub mod learner;
-
-
+pub mod rules;
+pub mod insurance;
+pub mod composition;
+pub mod counting;
+pub mod bankroll;
+pub mod betting;
+pub mod basic_strategy;
+pub mod evaluation;
+pub mod solver;
+pub mod dealer;
+pub mod mdp;
+pub mod double_q;
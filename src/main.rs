@@ -1,74 +1,180 @@
+#![allow(clippy::needless_return)]
+
+use std::env;
 use std::io::stdin;
 use std::time::Instant;
 
-//use clap::Parser;
-
+use blackjack_rl::bankroll::{Bankroll, BettingStrategy};
+use blackjack_rl::basic_strategy::BasicStrategy;
+use blackjack_rl::blackjack_agent::{Count, Features};
+use blackjack_rl::counting::{print_counting_systems, WongHalves};
+use blackjack_rl::dealer::print_dealer_table;
+use blackjack_rl::deck::Deck;
+use blackjack_rl::double_q::{double_q_learning, print_bias_report};
+use blackjack_rl::evaluation::{compare_shuffling, evaluate_sessions, print_playing_efficiency};
+use blackjack_rl::mdp::{print_planning, Mdp};
 use blackjack_rl::monte_carlo::monte_carlo;
-use blackjack_rl::sarsa::{sarsa, sarsamax};
-
-use crate::deck::Deck;
-use crate::round::{Outcome, RoundState};
+use blackjack_rl::round::{Insurance, Outcome, RoundError, RoundState};
+use blackjack_rl::rules::{Shuffling, TableRules};
+use blackjack_rl::sarsa::{compare_modes, expected_sarsa, sarsa, sarsamax};
+use blackjack_rl::solver::InfiniteDeckSolver;
+
+const USAGE: &str = "usage: blackjack_rl [mode] [options]
+modes:
+  play            play a round against the dealer
+  dealer          print the dealer's final totals for each up card, which the infinite deck solver is built on
+  planning        plan the values with the model of the game, to benchmark the learners against
+  counting        compare the systems' betting correlations and their playing efficiency in simulation
+  shuffling       compare what the count is worth to the play with a cut card and with a continuous shuffler
+  modes           compare the win rate and error curves of the three temporal difference modes
+  bias            print how much Double Q-learning lowers the overestimate of the values that Q-learning makes
+  monte-carlo, sarsa, sarsamax, expected-sarsa, double-q
+                  learn a strategy and play it for money, with SARSA if no mode is given
+options:
+  --continuous    deal from a continuous shuffler
+  --closest       learn the game the infinite deck values come closest to, and compare the learnt values with the exact ones
+  --composition   learn a composition-dependent strategy and report where it differs from the total-dependent one
+  --count         learn to deviate from basic strategy with the true count, on a six deck shoe
+  --halves        count with Wong Halves rather than Hi-Lo
+  --bet=<flat|spread|kelly|learnt>
+                  how to bet the learnt strategy, a spread only pays off with a count and a shoe that is not continuously shuffled
+  --sessions      size a bankroll from many sessions of the learnt strategy and of basic strategy";
+
+fn play(rules: TableRules) {
+    println!("Welcome to Simple Blackjack");
+    let mut deck = Deck::new_shuffled_decks(rules.decks);
+    let mut round = RoundState::new(&mut deck, &rules).unwrap();
 
-pub mod round;
-pub mod deck;
-pub mod hand;
+    //only the hands are shown, the round state also holds the dealer's hole card
+    println!("Cards are dealt, player: {}, dealer: {}", round.player, round.dealer);
 
-fn play() {
-    println!("Welcome to Simple Blackjack");
-    let mut deck = Deck::new_shuffled();
-    let mut round = RoundState::new(&mut deck);
+    if round.insurance == Insurance::Offered {
+        println!("The dealer shows an ace. Take insurance (y/n)? ");
+        let mut choice = String::new();
+        stdin().read_line(&mut choice).unwrap();
 
-    println!("Cards are dealt: {:?}", round);
+        round = round.insure(choice.trim().to_lowercase() == "y", &mut deck).unwrap();
+    }
 
     while !round.finished() {
-        println!("Current round state: {:?}", round);
-        println!("Hit (h) or Stand (s)? ");
+        println!("Player: {}, dealer: {}", round.player, round.dealer);
+        print!("Hit (h), Stand (s)");
+        if round.can_double() {
+            print!(", Double (d)");
+        }
+        if round.can_split() {
+            print!(", Split (p)");
+        }
+        if round.can_surrender() {
+            print!(", Surrender (r)");
+        }
+        println!("? ");
         let mut choice = String::new();
         stdin().read_line(&mut choice).unwrap();
 
         choice = choice.trim().to_lowercase();
-        match choice.as_str() {
-            "h" => round = round.hit(&mut deck).unwrap(),
-            "s" => round = round.stand(&mut deck).unwrap(),
-            _ => println!("Invalid option {:?}", choice)
+        let next = match choice.as_str() {
+            "h" => round.hit(&mut deck),
+            "s" => round.stand(&mut deck),
+            "d" => round.double(&mut deck),
+            "p" => round.split(&mut deck),
+            "r" => round.surrender(&mut deck),
+            _ => Err(RoundError::NotAllowed)
+        };
+        match next {
+            Ok(next_round) => round = next_round,
+            Err(error) => println!("Invalid option {:?}: {}", choice, error)
         }
     }
 
-    println!("Finished: {:?}", round);
+    println!("Finished, dealer: {}", round.dealer);
+    for played in &round.hands {
+        println!("Hand: {}, stake: {}, {:?}", played.hand, played.stake, played.outcome);
+    }
+    if round.insurance_won() {
+        println!("The dealer had a blackjack, the insurance pays 2:1.");
+    }
     match round.outcome {
         Outcome::Won => println!("Congratulations! You won!"),
+        Outcome::Blackjack => println!("Blackjack! Congratulations! You won!"),
         Outcome::Lost => println!("Sorry! You lost."),
+        Outcome::Surrendered => println!("You surrendered half of your bet."),
         Outcome::Draw => println!("It's a Draw!"),
         Outcome::Playing => println!("Invalid state, the game has not finished yet.")
     }
 }
 
-
-
-// #[derive(Parser, Debug)]
-// #[clap(author, version, about, long_about = None)]
-// struct Args {
-//     /// Name of the person to greet
-//     #[clap(short, long)]
-//     mode: String,
-//
-//     /// Number of times to greet
-//     #[clap(short, long, default_value_t = 1)]
-//     count: u8,
-// }
-
 fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mode = args.first().filter(|arg| !arg.starts_with("--")).map_or("sarsa", |arg| arg.as_str());
+    let option = |name: &str| args.iter().any(|arg| arg == name);
+
+    let mut rules = TableRules::default();
+    let mut features = Features::default();
+    if option("--continuous") {
+        rules.shuffling = Shuffling::Continuous;
+    }
+    if option("--count") {
+        rules.decks = 6;
+        features.count = Count::True;
+    }
+    if option("--closest") {
+        rules = InfiniteDeckSolver::closest_game(&rules);
+    }
+    features.composition = option("--composition");
+    if option("--halves") {
+        features.counting = &WongHalves;
+    }
 
-    //let args = Args::parse();
-
-    //todo: parse command line parameters with an API such as https://crates.io/crates/clap
-    //play();
     let start = Instant::now();
-//    let learner = monte_carlo();
-    let learner = sarsa();
-//  let learner = sarsamax(); //q-learning
+    let learner = match mode {
+        "play" => return play(rules),
+        "dealer" => return print_dealer_table(&rules),
+        "planning" => return print_planning(&rules),
+        "counting" => {
+            print_counting_systems();
+            return print_playing_efficiency(TableRules { decks: 6, ..rules }, 1000000, 200);
+        }
+        "shuffling" => return compare_shuffling(rules, 1000000, 200),
+        "modes" => return compare_modes(rules, features),
+        "bias" => return print_bias_report(rules, features),
+        "monte-carlo" => monte_carlo(rules, features),
+        "sarsa" => sarsa(rules, features),
+        "sarsamax" => sarsamax(rules, features),
+        "expected-sarsa" => expected_sarsa(rules, features),
+        "double-q" => double_q_learning(rules, features),
+        _ => return println!("{}", USAGE)
+    };
     let dur = start.elapsed();
     println!("Total time: {:?}", dur);
 
     learner.print_strategy();
+    if features.composition {
+        learner.print_composition_report();
+    }
+    if features.count == Count::True {
+        learner.print_count_strategy();
+    }
+
+    learner.print_bet_ramp();
+    //the learnt values against the exact ones, only a fair comparison when learnt on the closest game
+    if option("--closest") {
+        learner.print_exact_comparison(&InfiniteDeckSolver::new(&rules).q_table());
+        learner.print_exact_comparison(&Mdp::new(&rules).policy_iteration(1e-9).first_decisions());
+    }
+
+    //play the learnt strategy for money
+    let strategy = match args.iter().find_map(|arg| arg.strip_prefix("--bet=")) {
+        Some("spread") => BettingStrategy::Spread { unit: 10.0, spread: 8 },
+        Some("kelly") => BettingStrategy::Kelly { fraction: 0.5, minimum: 10.0 },
+        Some("learnt") => BettingStrategy::Learnt { unit: 10.0, ramp: learner.bet_ramp() },
+        _ => BettingStrategy::Flat { bet: 10.0 }
+    };
+    let bankroll = learner.simulate(&rules, &features, &strategy, Bankroll::new(10000.0), 100000);
+    bankroll.print_summary(&strategy);
+
+    if option("--sessions") {
+        evaluate_sessions(&learner, &rules, &features, &strategy, 10000.0, 10000, 1000).print(&strategy);
+        evaluate_sessions(&BasicStrategy, &rules, &features, &strategy, 10000.0, 10000, 1000).print(&strategy);
+    }
 }
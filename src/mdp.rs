@@ -0,0 +1,422 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::Instant;
+
+use crate::blackjack_agent::{BlackjackAction, BlackjackState};
+use crate::dealer::{add, DealerOutcomes, INFINITE_DECK};
+use crate::qtable::{QTable, State, StateAction};
+use crate::rules::{HoleCard, Surrender, TableRules};
+
+/// Where in the hand a decision is made, which decides the actions the player has.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum Stage {
+    /// the first two cards, where any action the rules allow can be taken
+    First,
+    /// the two cards of a hand that was split, which can be doubled if the rules allow it but not split again
+    Split,
+    /// a hand that was hit, which can only be hit again or stood on
+    Drawn,
+}
+
+/// A state of the MDP, the state the agent sees along with where in the hand it is.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub struct MdpState {
+    pub state: BlackjackState,
+    pub stage: Stage,
+}
+
+impl State for MdpState {}
+
+/// Where an action leads with some probability.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Transition {
+    pub probability: f64,
+    /// how many stakes the hand is played for from then on, 2 after a double and for the two hands of a split
+    pub stakes: f64,
+    /// the expected reward for each stake when the hand ends, 0 when it goes on
+    pub reward: f64,
+    /// the state the hand goes on in, None when it ends
+    pub next: Option<MdpState>,
+}
+
+/// The game as an explicit Markov decision process over the total-dependent states, with the transition
+/// probabilities worked out from the odds of drawing each card, so that the values can be planned rather than learnt.
+/// Like the infinite deck solver, each card is drawn with the same probabilities however many have been dealt, a split hand
+/// is not split again and the dealer's play is folded into the expected reward of standing.
+pub struct Mdp {
+    rules: TableRules,
+    /// the probability of drawing each value, from the ace to the ten
+    probabilities: [f64; 10],
+    /// what the dealer ends up with for each up card, indexed by its value (an ace is 11)
+    dealer: [DealerOutcomes; 12],
+    /// the states that can be reached from the first two cards, in the order they were found
+    states: Vec<MdpState>,
+    /// the actions in each state, and where each one leads
+    transitions: HashMap<MdpState, Vec<(BlackjackAction, Vec<Transition>)>>,
+}
+
+/// The values planned for the MDP.
+pub struct Plan {
+    pub q_table: QTable<MdpState, BlackjackAction>,
+    /// how many times the values of all the states were updated
+    pub sweeps: usize,
+    /// how many times the policy was improved: after every sweep of value iteration,
+    /// and after every policy evaluation of policy iteration
+    pub iterations: usize,
+}
+
+impl Plan {
+    /// returns the value of the state under the best action
+    pub fn value(&self, state: &MdpState) -> f64 {
+        return self.q_table.select_greedy_action(state)
+            .map(|action| self.q_table.get_value(&StateAction { agent_state: *state, action }))
+            .unwrap_or_default();
+    }
+
+    /// returns the values of the decisions on the first two cards, as a table to compare the learnt ones with
+    pub fn first_decisions(&self) -> QTable<BlackjackState, BlackjackAction> {
+        let mut q_table = QTable::new(0.0);
+        for (state_action, value) in self.q_table.get_all_values() {
+            if state_action.agent_state.stage == Stage::First {
+                q_table.update_value(&StateAction { agent_state: state_action.agent_state.state, action: state_action.action }, value);
+            }
+        }
+        return q_table;
+    }
+}
+
+impl Mdp {
+    /// returns the MDP for an infinite deck
+    pub fn new(rules: &TableRules) -> Mdp {
+        return Mdp::with_probabilities(rules, INFINITE_DECK);
+    }
+
+    /// returns the MDP drawing each value, from the ace to the ten, with the specified probability
+    pub fn with_probabilities(rules: &TableRules, probabilities: [f64; 10]) -> Mdp {
+        let mut mdp = Mdp { rules: *rules, probabilities, dealer: [DealerOutcomes::default(); 12], states: Vec::new(), transitions: HashMap::new() };
+        for up in 2..=11 {
+            mdp.dealer[up] = DealerOutcomes::faced(up as u8, rules, &probabilities);
+        }
+
+        let mut queue = VecDeque::new();
+        for up in 2u8..=11 {
+            for first in 1u8..=10 {
+                for second in 1u8..=10 {
+                    queue.extend(mdp.first_state(first, second, up));
+                }
+            }
+        }
+        while let Some(state) = queue.pop_front() {
+            if mdp.transitions.contains_key(&state) {
+                continue;
+            }
+
+            let actions = mdp.actions(&state);
+            for (_, transitions) in &actions {
+                queue.extend(transitions.iter().filter_map(|transition| transition.next));
+            }
+            mdp.states.push(state);
+            mdp.transitions.insert(state, actions);
+        }
+        return mdp;
+    }
+
+    /// returns the states that can be reached from the first two cards
+    pub fn states(&self) -> &[MdpState] {
+        return &self.states;
+    }
+
+    /// returns the actions in the state and where each one leads
+    pub fn transitions(&self, state: &MdpState) -> &[(BlackjackAction, Vec<Transition>)] {
+        return &self.transitions[state];
+    }
+
+    /// sweeps over the states updating each one with the value of its best action,
+    /// until no value changes by more than theta
+    pub fn value_iteration(&self, theta: f64) -> Plan {
+        let mut values: HashMap<MdpState, f64> = self.states.iter().map(|state| (*state, 0.0)).collect();
+        let mut sweeps = 0;
+        loop {
+            sweeps += 1;
+            let mut delta: f64 = 0.0;
+            for state in &self.states {
+                let value = self.transitions[state].iter()
+                    .map(|(_, transitions)| Mdp::action_value(transitions, &values))
+                    .fold(f64::NEG_INFINITY, f64::max);
+                delta = delta.max((value - values[state]).abs());
+                values.insert(*state, value);
+            }
+
+            if delta < theta {
+                break;
+            }
+        }
+
+        return Plan { q_table: self.q_values(&values), sweeps, iterations: sweeps };
+    }
+
+    /// evaluates a policy until no value changes by more than theta and then makes it greedy in those values,
+    /// starting from standing whenever it is allowed, until the policy no longer changes
+    pub fn policy_iteration(&self, theta: f64) -> Plan {
+        let mut policy: HashMap<MdpState, BlackjackAction> = self.states.iter()
+            .map(|state| {
+                let actions = &self.transitions[state];
+                let stand = actions.iter().find(|(action, _)| *action == BlackjackAction::Stand);
+                (*state, stand.unwrap_or(&actions[0]).0)
+            })
+            .collect();
+        let mut values: HashMap<MdpState, f64> = self.states.iter().map(|state| (*state, 0.0)).collect();
+        let mut sweeps = 0;
+        let mut iterations = 0;
+        loop {
+            loop {
+                sweeps += 1;
+                let mut delta: f64 = 0.0;
+                for state in &self.states {
+                    let value = Mdp::action_value(self.transitions_of(state, policy[state]), &values);
+                    delta = delta.max((value - values[state]).abs());
+                    values.insert(*state, value);
+                }
+
+                if delta < theta {
+                    break;
+                }
+            }
+
+            iterations += 1;
+            let mut stable = true;
+            for state in &self.states {
+                let current = Mdp::action_value(self.transitions_of(state, policy[state]), &values);
+                let (best, value) = self.transitions[state].iter()
+                    .map(|(action, transitions)| (*action, Mdp::action_value(transitions, &values)))
+                    .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+                    .unwrap();
+                //only a clearly better action replaces the current one, so that ties do not keep the policy changing
+                if value > current + theta {
+                    policy.insert(*state, best);
+                    stable = false;
+                }
+            }
+
+            if stable {
+                break;
+            }
+        }
+
+        return Plan { q_table: self.q_values(&values), sweeps, iterations };
+    }
+
+    /// returns the expected return of a round played with the planned values before the cards are dealt,
+    /// never taking insurance
+    pub fn round_value(&self, plan: &Plan) -> f64 {
+        let mut value = 0.0;
+        for up in 2u8..=11 {
+            let natural = DealerOutcomes::with_probabilities(up, &self.rules, &self.probabilities).natural;
+            for first in 1u8..=10 {
+                for second in 1u8..=10 {
+                    let probability = self.probability(if up == 11 { 1 } else { up }) * self.probability(first) * self.probability(second);
+                    value += probability * match self.first_state(first, second, up) {
+                        None => self.rules.blackjack_payout * (1.0 - natural),
+                        //a peeking dealer ends the round on a natural before the player acts
+                        Some(state) if self.rules.hole_card == HoleCard::Peek => -natural + (1.0 - natural) * plan.value(&state),
+                        Some(state) => plan.value(&state)
+                    };
+                }
+            }
+        }
+        return value;
+    }
+
+    /// returns the probability of drawing the value, where an ace is 1
+    fn probability(&self, value: u8) -> f64 {
+        return self.probabilities[value as usize - 1];
+    }
+
+    /// returns the state of the first two cards against the up card, None for a natural
+    fn first_state(&self, first: u8, second: u8, up: u8) -> Option<MdpState> {
+        let (total, soft) = add(0, false, first);
+        let (total, soft) = add(total, soft, second);
+        if total == 21 {
+            return None;
+        }
+
+        let pair = first == second && self.rules.max_splits > 0;
+        let state = BlackjackState { player: total, dealer: up, ace: soft, pair, composition: None, count: None };
+        return Some(MdpState { state, stage: Stage::First });
+    }
+
+    /// returns the actions the agent can choose from in the state, leaving out standing below 12 and hitting a 21
+    /// like the learners do, and where each one leads
+    fn actions(&self, state: &MdpState) -> Vec<(BlackjackAction, Vec<Transition>)> {
+        let hand = &state.state;
+        let (total, soft, up) = (hand.player, hand.ace, hand.dealer);
+        let stand = vec![Transition { probability: 1.0, stakes: 1.0, reward: self.dealer[up as usize].stand(total), next: None }];
+        if total == 21 {
+            return vec![(BlackjackAction::Stand, stand)];
+        }
+
+        let mut actions = vec![(BlackjackAction::Hit, self.draw(hand, total, soft, 1.0, |state| Some(MdpState { state, stage: Stage::Drawn })))];
+        if total >= 12 {
+            actions.push((BlackjackAction::Stand, stand));
+        }
+
+        let doubles = state.stage == Stage::First || (state.stage == Stage::Split && self.rules.double_after_split);
+        if doubles && self.rules.double.allows(total, soft) {
+            actions.push((BlackjackAction::Double, self.draw(hand, total, soft, 2.0, |_| None)));
+        }
+
+        if state.stage == Stage::First && hand.pair {
+            let (total, soft) = add(0, false, if soft { 1 } else { total / 2 });
+            //split aces only get one more card each
+            let split = if soft {
+                self.draw(hand, total, soft, 2.0, |_| None)
+            } else {
+                self.draw(hand, total, soft, 2.0, |state| Some(MdpState { state, stage: Stage::Split }))
+            };
+            actions.push((BlackjackAction::Split, split));
+        }
+
+        if state.stage == Stage::First && self.rules.surrender != Surrender::None {
+            let reward = -0.5 - 0.5 * self.dealer[up as usize].natural;
+            actions.push((BlackjackAction::Surrender, vec![Transition { probability: 1.0, stakes: 1.0, reward, next: None }]));
+        }
+        return actions;
+    }
+
+    /// returns where drawing a card to the total leads for the stakes, given the state the hand goes on in if it does not bust.
+    /// A hand that does not go on is stood on.
+    fn draw<F>(&self, hand: &BlackjackState, total: u8, soft: bool, stakes: f64, next: F) -> Vec<Transition>
+        where F: Fn(BlackjackState) -> Option<MdpState> {
+        return self.probabilities.iter().enumerate()
+            .map(|(i, probability)| {
+                let (total, soft) = add(total, soft, i as u8 + 1);
+                let next = if total > 21 { None } else { next(BlackjackState { player: total, ace: soft, pair: false, ..*hand }) };
+                let reward = if next.is_some() { 0.0 } else { self.dealer[hand.dealer as usize].stand(total) };
+                Transition { probability: *probability, stakes, reward, next }
+            })
+            .collect();
+    }
+
+    fn transitions_of(&self, state: &MdpState, action: BlackjackAction) -> &[Transition] {
+        return &self.transitions[state].iter().find(|(a, _)| *a == action).unwrap().1;
+    }
+
+    fn action_value(transitions: &[Transition], values: &HashMap<MdpState, f64>) -> f64 {
+        return transitions.iter()
+            .map(|transition| transition.probability * transition.stakes * (transition.reward + transition.next.map_or(0.0, |next| values[&next])))
+            .sum();
+    }
+
+    fn q_values(&self, values: &HashMap<MdpState, f64>) -> QTable<MdpState, BlackjackAction> {
+        let mut q_table = QTable::new(0.0);
+        for state in &self.states {
+            for (action, transitions) in &self.transitions[state] {
+                q_table.update_value(&StateAction { agent_state: *state, action: *action }, Mdp::action_value(transitions, values));
+            }
+        }
+        return q_table;
+    }
+}
+
+/// plans the values of the game for the rules with value iteration and with policy iteration,
+/// and prints how much work each one took and what a round is worth with the policy it found
+pub fn print_planning(rules: &TableRules) {
+    let theta = 1e-9;
+    let mdp = Mdp::new(rules);
+
+    println!("\nPlanning over {} states:", mdp.states().len());
+    println!("method           | iterations | sweeps | time         | round value");
+    println!("---------------------------------------------------------------------");
+    let start = Instant::now();
+    let value_iteration = mdp.value_iteration(theta);
+    let duration = start.elapsed();
+    println!("value iteration  | {:>10} | {:>6} | {:>12?} | {:+.5}", value_iteration.iterations, value_iteration.sweeps, duration, mdp.round_value(&value_iteration));
+
+    let start = Instant::now();
+    let policy_iteration = mdp.policy_iteration(theta);
+    let duration = start.elapsed();
+    println!("policy iteration | {:>10} | {:>6} | {:>12?} | {:+.5}", policy_iteration.iterations, policy_iteration.sweeps, duration, mdp.round_value(&policy_iteration));
+    println!("---------------------------------------------------------------------");
+
+    let agreeing = mdp.states().iter()
+        .filter(|state| value_iteration.q_table.select_greedy_action(state) == policy_iteration.q_table.select_greedy_action(state))
+        .count();
+    println!("the policies agree in {} of {} states", agreeing, mdp.states().len());
+    println!();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::solver::InfiniteDeckSolver;
+    use super::*;
+
+    fn first(player: u8, dealer: u8, ace: bool, pair: bool) -> MdpState {
+        return MdpState { state: BlackjackState { player, dealer, ace, pair, composition: None, count: None }, stage: Stage::First };
+    }
+
+    #[test]
+    fn test_transitions() {
+        let mdp = Mdp::new(&TableRules::default());
+        for state in mdp.states() {
+            for (action, transitions) in mdp.transitions(state) {
+                let total: f64 = transitions.iter().map(|transition| transition.probability).sum();
+                assert!((total - 1.0).abs() < 1e-9, "{:?} {:?}", state, action);
+            }
+        }
+
+        //a hard 10 after a hit can only be hit, the first two cards can also be doubled
+        let drawn = MdpState { stage: Stage::Drawn, ..first(10, 6, false, false) };
+        let actions: Vec<BlackjackAction> = mdp.transitions(&drawn).iter().map(|(action, _)| *action).collect();
+        assert_eq!(vec![BlackjackAction::Hit], actions);
+        let actions: Vec<BlackjackAction> = mdp.transitions(&first(10, 6, false, false)).iter().map(|(action, _)| *action).collect();
+        assert_eq!(vec![BlackjackAction::Hit, BlackjackAction::Double], actions);
+
+        //split aces draw one card each and end
+        let (_, split) = mdp.transitions(&first(12, 6, true, true)).iter().find(|(action, _)| *action == BlackjackAction::Split).unwrap();
+        assert!(split.iter().all(|transition| transition.next.is_none() && transition.stakes == 2.0));
+    }
+
+    #[test]
+    fn test_value_iteration_matches_solver() {
+        let rules = TableRules { surrender: Surrender::Late, ..TableRules::default() };
+        let exact = InfiniteDeckSolver::new(&rules).q_table();
+        let planned = Mdp::new(&rules).value_iteration(1e-12).first_decisions();
+
+        let mut compared = 0;
+        for (state_action, value) in planned.get_all_values() {
+            if exact.get_actions(&state_action.agent_state).contains(&state_action.action) {
+                assert!((value - exact.get_value(&state_action)).abs() < 1e-9, "{:?}", state_action);
+                compared += 1;
+            }
+        }
+        assert!(compared > 500, "{}", compared);
+    }
+
+    #[test]
+    fn test_policy_iteration_matches_value_iteration() {
+        let mdp = Mdp::new(&TableRules::default());
+        let value_iteration = mdp.value_iteration(1e-12);
+        let policy_iteration = mdp.policy_iteration(1e-12);
+
+        //the MDP has no cycles, so value iteration settles as soon as the values have reached back from the longest hands
+        assert!(value_iteration.sweeps < 20, "{}", value_iteration.sweeps);
+        assert!(policy_iteration.iterations > 1);
+        for state in mdp.states() {
+            assert!((value_iteration.value(state) - policy_iteration.value(state)).abs() < 1e-9, "{:?}", state);
+        }
+        assert_eq!(BlackjackAction::Hit, policy_iteration.q_table.select_greedy_action(&first(16, 10, false, false)).unwrap());
+        assert_eq!(BlackjackAction::Double, policy_iteration.q_table.select_greedy_action(&first(11, 6, false, false)).unwrap());
+    }
+
+    #[test]
+    fn test_round_value() {
+        //the house keeps about half a percent from a basic strategy player of an infinite deck with these rules
+        let mdp = Mdp::new(&TableRules::default());
+        let value = mdp.round_value(&mdp.value_iteration(1e-9));
+        assert!(value < -0.003 && value > -0.008, "{}", value);
+
+        //6:5 blackjacks cost the player about 1.4%
+        let six_to_five = Mdp::new(&TableRules { blackjack_payout: 1.2, ..TableRules::default() });
+        let cost = value - six_to_five.round_value(&six_to_five.value_iteration(1e-9));
+        assert!((cost - 0.3 * 0.0475).abs() < 0.001, "{}", cost);
+    }
+}
@@ -1,36 +1,44 @@
 use std::collections::VecDeque;
 
-use crate::blackjack_agent::{BlackjackAction, BlackjackState, EpisodeResult};
+use crate::betting::{learn_bets, BetAction, BetState};
+use crate::blackjack_agent::{act, available_actions, BlackjackAction, BlackjackState, EpisodeResult, Features};
 use crate::blackjack_policy::{e_greedy_policy, random_policy};
-use crate::deck::Deck;
+use crate::insurance::{insurance_episode, learn_episode_insurance, InsuranceAction, InsuranceState};
 use crate::qtable::{QTable, StateAction};
-use crate::round::RoundState;
 use crate::learner::Learner;
+use crate::rules::TableRules;
+use crate::shoe::Shoe;
 
-pub fn monte_carlo() -> Learner {
+pub fn monte_carlo(rules: TableRules, features: Features) -> Learner {
     println!("Running in Monte Carlo mode");
-    Learner::new_trained(evaluate_episode)
+    //the shoe is kept between episodes, so that the count carries over until the cut card comes out
+    let mut shoe = Shoe::from(&rules);
+    shoe.set_counting(features.counting);
+    Learner::new_trained(|q_table, insurance_table, bet_table, episode_number| evaluate_episode(q_table, insurance_table, bet_table, &mut shoe, &rules, &features, episode_number))
 }
 
-pub fn evaluate_episode(q_table: &mut QTable<BlackjackState, BlackjackAction>, episode_number: usize) -> (i32, f64) {
-    let mut deck = Deck::new_shuffled();
-    let result = episode(&mut deck, q_table, episode_number);
+pub fn evaluate_episode(q_table: &mut QTable<BlackjackState, BlackjackAction>, insurance_table: &mut QTable<InsuranceState, InsuranceAction>,
+                        bet_table: &mut QTable<BetState, BetAction>, shoe: &mut Shoe, rules: &TableRules, features: &Features, episode_number: usize) -> (f64, f64) {
+    let result = episode(shoe, rules, features, q_table, insurance_table, episode_number);
+
+    learn_episode_insurance(insurance_table, &result);
+    //the bet is settled with the insurance as well, the playing decisions only with the hands
+    let round_reward = result.round_reward();
+    learn_bets(bet_table, &result.bet, round_reward);
 
     let mut sum_error = 0.0;
     let state_action_count = result.state_actions.len();
     for state_action in result.state_actions {
-        if state_action.agent_state.player > 11 && state_action.agent_state.player < 21 {
-            let old_value = q_table.get_value(&state_action);
-            let count = q_table.get_count(&state_action) + 1;
+        let old_value = q_table.get_value(&state_action);
+        let count = q_table.get_count(&state_action) + 1;
 
-            let g = result.reward as f64;
+        let g = result.reward;
 
-            let error = g - old_value;
-            let new_value = old_value + (error / count as f64);
+        let error = g - old_value;
+        let new_value = old_value + (error / count as f64);
 
-            sum_error = sum_error + f64::abs(error);
-            q_table.update_value(&state_action, new_value);
-        }
+        sum_error += f64::abs(error);
+        q_table.update_value(&state_action, new_value);
     }
 
     let mean_error = if state_action_count == 0 {
@@ -39,36 +47,39 @@ pub fn evaluate_episode(q_table: &mut QTable<BlackjackState, BlackjackAction>, e
         sum_error / state_action_count as f64
     };
 
-    (result.reward, mean_error)
+    (round_reward, mean_error)
 }
 
 
-pub fn episode(deck: &mut Deck, q_table: &QTable<BlackjackState, BlackjackAction>, episode_number: usize) -> EpisodeResult {
+pub fn episode(shoe: &mut Shoe, rules: &TableRules, features: &Features, q_table: &QTable<BlackjackState, BlackjackAction>,
+               insurance_table: &QTable<InsuranceState, InsuranceAction>, episode_number: usize) -> EpisodeResult {
     let mut state_actions: VecDeque<StateAction<BlackjackState, BlackjackAction>> = VecDeque::new();
 
-    let mut round_state = RoundState::new(deck);
+    //the bet is placed on the count before the round is dealt
+    shoe.shuffle_if_due();
+    let bet = BetState::from(shoe, features);
+    let (mut round_state, insurance) = insurance_episode(shoe.new_round(rules).unwrap(), shoe.deck(), features, insurance_table, episode_number);
 
     let mut random_start = false;
 
     while !round_state.finished() {
-        let agent_state = BlackjackState::from(&round_state);
+        let agent_state = BlackjackState::from_features(&round_state, features);
+        let actions = available_actions(&round_state);
         let action = if random_start {
             random_start = false;
-            random_policy(&agent_state, q_table, episode_number)
+            random_policy(&agent_state, &actions, q_table, episode_number)
         } else {
-            e_greedy_policy(&agent_state, q_table, episode_number)
+            e_greedy_policy(&agent_state, &actions, q_table, episode_number)
         };
 
         //we push them to the front so that the last state-action pair are at the front
         state_actions.push_front(StateAction { agent_state, action });
 
-        match action {
-            BlackjackAction::Hit => round_state = round_state.hit(deck).unwrap(),
-            BlackjackAction::Stand => round_state = round_state.stand(deck).unwrap()
-        }
+        round_state = act(&round_state, action, shoe.deck()).unwrap();
     }
 
-    return EpisodeResult::from(&round_state, state_actions);
+    shoe.end_round(&round_state);
+    return EpisodeResult::from(&round_state, state_actions, insurance, bet);
 }
 
 
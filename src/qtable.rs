@@ -8,6 +8,7 @@ pub trait State: Eq + Hash + Clone + Debug {}
 pub trait Action: Eq + Hash + Clone + Debug {}
 
 #[derive(Debug, Copy, Clone, Hash, Eq)]
+#[allow(clippy::derived_hash_with_manual_eq)]
 pub struct StateAction<S: State, A: Action> {
     pub agent_state: S,
     pub action: A,
@@ -33,21 +34,21 @@ impl<S: State, A: Action> QTable<S, A> {
     pub fn get_value(&self, state_action: &StateAction<S, A>) -> f64 {
         self.q_values.get(&state_action.agent_state)
             .and_then(|map| map.get(&state_action.action))
-            .map(|a| *a)
+            .copied()
             .unwrap_or(self.default_value)
     }
 
     pub fn get_count(&self, state_action: &StateAction<S, A>) -> usize {
         self.counts
             .get(state_action)
-            .cloned()
+            .copied()
             .unwrap_or_default()
     }
 
     pub fn update_value(&mut self, state_action: &StateAction<S, A>, new_value: f64) {
         let state_action_values = self.q_values
             .entry(state_action.agent_state.clone())
-            .or_insert_with(|| HashMap::new());
+            .or_default();
 
         state_action_values.insert(state_action.action.clone(), new_value);
         *self.counts.entry(state_action.clone()).or_insert_with(|| 0) += 1;
@@ -59,7 +60,19 @@ impl<S: State, A: Action> QTable<S, A> {
         action_values
             .filter(|map| !map.is_empty())
             .and_then(|map| self.select_best_action(map))
-            .map(|opt| opt.clone())
+    }
+
+    /// like select_greedy_action, but only considers the specified actions
+    pub fn select_greedy_action_among(&self, agent_state: &S, actions: &[A]) -> Option<A> {
+        let action_values: Option<HashMap<A, f64>> = self.q_values.get(agent_state)
+            .map(|map| map.iter()
+                .filter(|(a, _)| actions.contains(a))
+                .map(|(a, v)| (a.clone(), *v))
+                .collect());
+
+        action_values
+            .filter(|map| !map.is_empty())
+            .and_then(|map| self.select_best_action(&map))
     }
 
     fn select_best_action(&self, action_values: &HashMap<A, f64>) -> Option<A> {
@@ -76,15 +89,61 @@ impl<S: State, A: Action> QTable<S, A> {
         // }
 
         let mut q: Vec<(StateAction<S, A>, f64)> = self.q_values.iter()
-            .map(|(k, l)|
-                l.iter().map(|(a, v)| (StateAction { agent_state: k.clone(), action: a.clone() }, v.clone())))
-            .flatten()
+            .flat_map(|(k, l)|
+                l.iter().map(|(a, v)| (StateAction { agent_state: k.clone(), action: a.clone() }, *v)))
             .collect();
 
         q.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
         return q;
     }
 
+    /// returns the states that have a value for at least one action
+    pub fn get_states(&self) -> Vec<S> {
+        return self.q_values.keys().cloned().collect();
+    }
+
+    /// returns the actions that have a value in the specified state
+    pub fn get_actions(&self, agent_state: &S) -> Vec<A> {
+        return self.q_values.get(agent_state)
+            .map(|map| map.keys().cloned().collect())
+            .unwrap_or_default();
+    }
+
+    /// returns the values of the states projected onto coarser states, averaging the values of the states
+    /// that share a projection weighted by how often each state-action was updated
+    pub fn aggregate<F>(&self, project: F) -> HashMap<S, HashMap<A, f64>>
+        where F: Fn(&S) -> S {
+        let mut sums: HashMap<S, HashMap<A, (f64, usize)>> = HashMap::new();
+        for (state, action_values) in &self.q_values {
+            for (action, value) in action_values {
+                let count = self.get_count(&StateAction { agent_state: state.clone(), action: action.clone() });
+                let sum = sums.entry(project(state))
+                    .or_default()
+                    .entry(action.clone())
+                    .or_insert((0.0, 0));
+                sum.0 += value * count as f64;
+                sum.1 += count;
+            }
+        }
+
+        return sums.into_iter()
+            .map(|(state, action_sums)| {
+                let values = action_sums.into_iter()
+                    .map(|(action, (sum, count))| (action, if count == 0 { self.default_value } else { sum / count as f64 }))
+                    .collect();
+                (state, values)
+            })
+            .collect();
+    }
+
+    /// like get_policy, but for the aggregated values of the projected states
+    pub fn get_aggregate_policy<F>(&self, project: F) -> HashMap<S, A>
+        where F: Fn(&S) -> S {
+        return self.aggregate(project).iter()
+            .filter_map(|(s, a)| self.select_best_action(a).map(|action| (s.clone(), action)))
+            .collect();
+    }
+
     pub fn get_policy(&self) -> HashMap<S,A> {
         self.q_values.iter()
             .map(|(s, a)| (s.clone(), self.select_best_action(a).unwrap()))
@@ -1,149 +1,533 @@
-use crate::round::Outcome::{Draw, Lost, Playing, Won};
+use std::cmp::Ordering;
+use std::error::Error;
+use std::fmt;
+
+use crate::round::Outcome::{Blackjack, Draw, Lost, Playing, Surrendered, Won};
+use crate::card::Card;
+use crate::counting::{decks_remaining, CountingSystem, HiLo, Rounding};
 use crate::deck::Deck;
 use crate::hand::Hand;
+use crate::rules::{HoleCard, Surrender, TableRules};
 
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone)]
 pub enum Outcome {
     Won,
+    /// the player won with a natural (an ace and a ten valued card as the first two cards)
+    Blackjack,
     Lost,
+    /// the player gave up the hand and lost half of the stake
+    Surrendered,
     Draw,
     Playing,
 }
 
-#[derive(Debug)]
+/// A hand the player has finished playing. Its outcome is Playing while it waits for the dealer to play.
+#[derive(Debug, Clone)]
+pub struct PlayedHand {
+    pub hand: Hand,
+    pub stake: u8,
+    pub outcome: Outcome,
+}
+
+/// Insurance is offered when the dealer shows an ace, before the player acts.
+/// For a player with a natural, taking insurance amounts to taking even money.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Insurance {
+    NotOffered,
+    /// the player still has to decide whether to take insurance
+    Offered,
+    Taken,
+    Declined,
+}
+
+/// Why an action could not be applied to a round.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RoundError {
+    /// the action is not allowed in the current state of the round
+    NotAllowed,
+    /// the deck ran out of cards while dealing
+    OutOfCards,
+}
+
+impl fmt::Display for RoundError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            RoundError::NotAllowed => write!(f, "the action is not allowed in this state of the round"),
+            RoundError::OutOfCards => write!(f, "the deck ran out of cards")
+        };
+    }
+}
+
+impl Error for RoundError {}
+
+#[derive(Debug, Clone)]
 pub struct RoundState {
     pub outcome: Outcome,
+    /// the hand currently being played, or the last hand played once the round is finished
     pub player: Hand,
+    /// the dealer's visible cards, the hole card is only added once it is revealed
     pub dealer: Hand,
-    pub hilo: i32,
+    /// the dealer's face down card, None with no hole card rules or once it is revealed
+    hole: Option<Card>,
+    /// the running count of the cards seen so far
+    pub count: i32,
+    /// the system the cards are counted with
+    pub counting: &'static dyn CountingSystem,
+    /// how many cards the player has not seen yet, those left in the deck and the hole card
+    unseen: usize,
+    pub rules: TableRules,
+    /// how many units the player has at stake on the current hand, 2 after doubling down
+    pub stake: u8,
+    /// how many times the player has split in this round
+    pub splits: u8,
+    /// split hands waiting for their second card, the last one is played next
+    pub waiting: Vec<Hand>,
+    /// the hands the player has finished playing
+    pub hands: Vec<PlayedHand>,
+    pub insurance: Insurance,
 }
 
 impl RoundState {
-    pub fn new_with_hilo(deck: &mut Deck, init_hilo: i32) -> RoundState {
-        let player_c1 = deal(deck);
-        let player_c2 = deal(deck);
+    /// deals a new round, counting the cards with Hi-Lo from the specified running count
+    pub fn new_with_hilo(deck: &mut Deck, rules: &TableRules, init_hilo: i32) -> Result<RoundState, RoundError> {
+        return RoundState::new_with_count(deck, rules, &HiLo, init_hilo);
+    }
+
+    /// deals a new round, counting the cards with the specified system from the specified running count
+    pub fn new_with_count(deck: &mut Deck, rules: &TableRules, counting: &'static dyn CountingSystem, init_count: i32) -> Result<RoundState, RoundError> {
+        let player_c1 = deal(deck)?;
+        let player_c2 = deal(deck)?;
         let player = Hand::from(player_c1, player_c2);
 
-        let dealer_card = deal(deck);
+        let dealer_card = deal(deck)?;
         let dealer = Hand::new().hit(dealer_card);
+        let hole = match rules.hole_card {
+            HoleCard::Peek => Some(deal(deck)?),
+            HoleCard::NoHoleCard => None
+        };
+
+        //the hole card is only counted once it is revealed
+        let count = init_count + counting.tag(dealer_card) + counting.tag(player_c1) + counting.tag(player_c2);
+
+        //the dealer shows an ace
+        let insurance = if dealer.sum == 11 { Insurance::Offered } else { Insurance::NotOffered };
 
-        let hilo = init_hilo + RoundState::card_hilo(dealer_card) +
-            RoundState::card_hilo(player_c1) + RoundState::card_hilo(player_c2);
+        let round_state = RoundState {
+            outcome: Outcome::Playing, player, dealer, hole, count, counting, unseen: 0, rules: *rules, stake: 1, splits: 0, waiting: Vec::new(), hands: Vec::new(), insurance,
+        };
 
-        RoundState { outcome: Outcome::Playing, player, dealer, hilo }
+        return if insurance == Insurance::Offered {
+            Ok(round_state.seen(deck))
+        } else {
+            Ok(round_state.settle_natural(deck)?.peek().seen(deck))
+        };
     }
 
-    pub fn new(deck: &mut Deck) -> RoundState {
-        RoundState::new_with_hilo(deck, 0)
+    pub fn new(deck: &mut Deck, rules: &TableRules) -> Result<RoundState, RoundError> {
+        RoundState::new_with_hilo(deck, rules, 0)
     }
 
-    pub fn hit(&self, deck: &mut Deck) -> Option<RoundState> {
-        return match self.outcome {
-            Playing => {
-                let (new_player_hand, new_hilo) = RoundState::hit_player(&self.player, deck, self.hilo);
-                return if new_player_hand.is_bust() {
-                    Some(RoundState { outcome: Outcome::Lost, player: new_player_hand, dealer: self.dealer, hilo: new_hilo })
-                } else {
-                    Some(RoundState { outcome: Outcome::Playing, player: new_player_hand, dealer: self.dealer, hilo: new_hilo })
-                };
-            }
+    /// takes or declines the insurance offered when the dealer shows an ace, which is settled
+    /// once the dealer's second card is revealed
+    pub fn insure(&self, take: bool, deck: &mut Deck) -> Result<RoundState, RoundError> {
+        return self.play_insure(take, deck).map(|round_state| round_state.seen(deck));
+    }
 
-            _ => None
+    pub fn hit(&self, deck: &mut Deck) -> Result<RoundState, RoundError> {
+        return self.play_hit(deck).map(|round_state| round_state.seen(deck));
+    }
+
+    pub fn stand(&self, deck: &mut Deck) -> Result<RoundState, RoundError> {
+        return self.play_stand(deck).map(|round_state| round_state.seen(deck));
+    }
+
+    /// doubles the stake, draws exactly one more card and then stands
+    pub fn double(&self, deck: &mut Deck) -> Result<RoundState, RoundError> {
+        return self.play_double(deck).map(|round_state| round_state.seen(deck));
+    }
+
+    /// splits a pair into two hands with the same stake, which are then played in turn.
+    /// Each split ace only gets one more card, unless it can be split again.
+    pub fn split(&self, deck: &mut Deck) -> Result<RoundState, RoundError> {
+        return self.play_split(deck).map(|round_state| round_state.seen(deck));
+    }
+
+    /// gives up the hand for half of the stake. With late surrender the dealer first checks for blackjack,
+    /// in which case the whole stake is lost.
+    pub fn surrender(&self, deck: &mut Deck) -> Result<RoundState, RoundError> {
+        return self.play_surrender(deck).map(|round_state| round_state.seen(deck));
+    }
+
+    /// returns the running count divided by the decks the player has not seen, rounded down
+    pub fn true_count(&self) -> i32 {
+        return self.true_count_rounded(Rounding::Floor);
+    }
+
+    /// returns the running count divided by the decks the player has not seen, rounded as specified
+    pub fn true_count_rounded(&self, rounding: Rounding) -> i32 {
+        return rounding.true_count(self.count, self.counting, self.unseen);
+    }
+
+    /// returns how many cards the player has not seen, the ones left in the shoe and the hole card
+    pub fn cards_remaining(&self) -> usize {
+        return self.unseen;
+    }
+
+    /// returns how many decks the cards the player has not seen make up
+    pub fn decks_remaining(&self) -> f64 {
+        return decks_remaining(self.unseen);
+    }
+
+    /// notes how many cards the player has not seen, once an action is over
+    fn seen(self, deck: &Deck) -> RoundState {
+        let unseen = deck.len() + if self.hole.is_some() { 1 } else { 0 };
+        return RoundState { unseen, ..self };
+    }
+
+    fn play_insure(&self, take: bool, deck: &mut Deck) -> Result<RoundState, RoundError> {
+        if self.insurance != Insurance::Offered {
+            return Err(RoundError::NotAllowed);
+        }
+
+        let insurance = if take { Insurance::Taken } else { Insurance::Declined };
+        return Ok(RoundState { insurance, ..self.clone() }.settle_natural(deck)?.peek());
+    }
+
+    /// returns true if insurance was taken and the dealer has a natural
+    pub fn insurance_won(&self) -> bool {
+        return self.insurance == Insurance::Taken && self.dealer.is_natural();
+    }
+
+    fn play_hit(&self, deck: &mut Deck) -> Result<RoundState, RoundError> {
+        if !self.can_hit() {
+            return Err(RoundError::NotAllowed);
+        }
+
+        let (player, count) = self.hit_hand(&self.player, deck, self.count)?;
+        let round_state = RoundState { player, count, ..self.clone() };
+        return if round_state.player.is_bust() {
+            round_state.finish_hand(Outcome::Lost, deck)
+        } else {
+            Ok(round_state)
         };
     }
 
-    pub fn stand(&self, deck: &mut Deck) -> Option<RoundState> {
-        return match self.outcome {
-            Playing => {
-                let (dealer, new_hilo) = RoundState::hit_dealer(&self.dealer, deck, self.hilo);
-                return if self.player > dealer {
-                    Some(RoundState { outcome: Outcome::Won, player: self.player, dealer, hilo: new_hilo })
-                } else if self.player == dealer {
-                    Some(RoundState { outcome: Outcome::Draw, player: self.player, dealer, hilo: new_hilo })
-                } else {
-                    Some(RoundState { outcome: Outcome::Lost, player: self.player, dealer, hilo: new_hilo })
-                };
-            }
+    fn play_stand(&self, deck: &mut Deck) -> Result<RoundState, RoundError> {
+        return if self.playing() {
+            self.finish_hand(Outcome::Playing, deck)
+        } else {
+            Err(RoundError::NotAllowed)
+        };
+    }
 
-            _ => None
+    fn play_double(&self, deck: &mut Deck) -> Result<RoundState, RoundError> {
+        if !self.can_double() {
+            return Err(RoundError::NotAllowed);
+        }
+
+        //with early surrender the dealer only peeks once the player decides to play on, before the stake is doubled
+        let peeked = self.clone().peek_before_acting();
+        if peeked.finished() {
+            return Ok(peeked);
+        }
+
+        let (player, count) = self.hit_hand(&self.player, deck, self.count)?;
+        let doubled = RoundState { player, count, stake: self.stake * 2, ..self.clone() };
+        return if doubled.player.is_bust() {
+            doubled.finish_hand(Outcome::Lost, deck)
+        } else {
+            doubled.play_stand(deck)
         };
     }
 
-    pub fn won(&self) -> bool {
-        return match self.outcome {
-            Won => true,
-            _ => false
+    fn play_split(&self, deck: &mut Deck) -> Result<RoundState, RoundError> {
+        if !self.can_split() {
+            return Err(RoundError::NotAllowed);
+        }
+
+        let peeked = self.clone().peek_before_acting();
+        if peeked.finished() {
+            return Ok(peeked);
+        }
+
+        //each card of the pair starts a hand of its own, the second one waits for its turn
+        let split_hand = Hand::new().hit(self.player.cards[0]);
+        let (player, count) = self.hit_hand(&split_hand, deck, self.count)?;
+        let mut waiting = self.waiting.clone();
+        waiting.push(Hand::new().hit(self.player.cards[1]));
+
+        let round_state = RoundState { player, count, splits: self.splits + 1, waiting, ..self.clone() };
+        return round_state.stand_split_aces(deck);
+    }
+
+    fn play_surrender(&self, deck: &mut Deck) -> Result<RoundState, RoundError> {
+        if !self.can_surrender() {
+            return Err(RoundError::NotAllowed);
+        }
+
+        let (dealer, count) = self.reveal_hole(deck)?;
+        //without a hole card the dealer only checks for blackjack now, which a late surrender does not escape
+        let outcome = if dealer.sum == 21 && self.rules.surrender == Surrender::Late {
+            Outcome::Lost
+        } else {
+            Outcome::Surrendered
         };
+        let hands = vec![PlayedHand { hand: self.player.clone(), stake: self.stake, outcome }];
+        return Ok(RoundState { outcome, dealer, hole: None, count, hands, ..self.clone() });
     }
 
-    pub fn lost(&self) -> bool {
-        return match self.outcome {
-            Lost => true,
-            _ => false
+    pub fn can_hit(&self) -> bool {
+        return self.playing() && !self.split_aces();
+    }
+
+    /// returns true if the rules allow the player to double down on the current hand
+    pub fn can_double(&self) -> bool {
+        if !self.can_hit() || self.player.card_count() != 2 || (self.splits > 0 && !self.rules.double_after_split) {
+            return false;
+        }
+
+        return self.rules.double.allows(self.player.sum, self.player.ace);
+    }
+
+    /// returns true if the current hand is a pair the rules allow to split
+    pub fn can_split(&self) -> bool {
+        return self.playing()
+            && self.player.is_pair()
+            && self.splits < self.rules.max_splits
+            && (!self.player.first_ace() || self.splits == 0 || self.rules.resplit_aces);
+    }
+
+    /// returns true if the rules offer surrender, which is only possible on the first two cards before splitting
+    pub fn can_surrender(&self) -> bool {
+        return self.playing()
+            && self.rules.surrender != Surrender::None
+            && self.splits == 0
+            && self.player.card_count() == 2;
+    }
+
+    /// returns true if the player can act on the current hand, which is only once insurance was decided
+    fn playing(&self) -> bool {
+        return !self.finished() && self.insurance != Insurance::Offered;
+    }
+
+    /// if the player has a natural, the dealer completes the hand to see whether it is a push
+    fn settle_natural(self, deck: &mut Deck) -> Result<RoundState, RoundError> {
+        if !self.player.is_natural() {
+            return Ok(self);
+        }
+
+        let (dealer, count) = self.reveal_hole(deck)?;
+        let outcome = if dealer.sum == 21 { Outcome::Draw } else { Outcome::Blackjack };
+        let hands = vec![PlayedHand { hand: self.player.clone(), stake: self.stake, outcome }];
+        return Ok(RoundState { outcome, dealer, hole: None, count, hands, ..self });
+    }
+
+    /// with a hole card the dealer peeks for a natural, which ends the round before the player acts.
+    /// With early surrender the peek waits until the player decides not to surrender.
+    fn peek(self) -> RoundState {
+        return if self.rules.surrender == Surrender::Early {
+            self
+        } else {
+            self.peek_before_acting()
         };
     }
 
-    pub fn draw(&self) -> bool {
-        return match self.outcome {
-            Draw => true,
-            _ => false
+    fn peek_before_acting(self) -> RoundState {
+        let dealer_natural = match self.hole {
+            Some(hole) => self.dealer.card_count() == 1 && self.dealer.hit(hole).sum == 21,
+            None => false
         };
+        if self.finished() || self.insurance == Insurance::Offered || !dealer_natural {
+            return self;
+        }
+
+        let dealer = self.dealer.hit(self.hole.unwrap());
+        let count = self.count + self.counting.tag(self.hole.unwrap());
+        let hands = vec![PlayedHand { hand: self.player.clone(), stake: self.stake, outcome: Outcome::Lost }];
+        return RoundState { outcome: Outcome::Lost, dealer, hole: None, count, hands, ..self };
     }
 
-    pub fn finished(&self) -> bool {
-        return match self.outcome {
-            Playing => false,
-            _ => true
+    /// turns over the hole card, or deals the dealer's second card when there is no hole card
+    fn reveal_hole(&self, deck: &mut Deck) -> Result<(Hand, i32), RoundError> {
+        return match self.hole {
+            Some(hole) => Ok((self.dealer.hit(hole), self.count + self.counting.tag(hole))),
+            None => self.hit_hand(&self.dealer, deck, self.count)
         };
     }
 
-    fn card_hilo(card: u8) -> i32 {
-        return if card >= 2 && card <= 6 {
-            1
-        } else if card >= 7 && card <= 9 {
-            0
+    /// returns true if the current hand is a split ace, which cannot be hit or doubled
+    fn split_aces(&self) -> bool {
+        return self.splits > 0 && self.player.first_ace();
+    }
+
+    /// a split ace stands as soon as it gets its second card, unless it can be split again
+    fn stand_split_aces(self, deck: &mut Deck) -> Result<RoundState, RoundError> {
+        return if self.split_aces() && !self.can_split() {
+            self.finish_hand(Outcome::Playing, deck)
+        } else {
+            Ok(self)
+        };
+    }
+
+    /// stores the current hand with its outcome so far, and moves on to the next split hand.
+    /// Once all hands are played the dealer plays and the round is settled.
+    fn finish_hand(&self, outcome: Outcome, deck: &mut Deck) -> Result<RoundState, RoundError> {
+        let mut hands = self.hands.clone();
+        hands.push(PlayedHand { hand: self.player.clone(), stake: self.stake, outcome });
+
+        let mut waiting = self.waiting.clone();
+        return match waiting.pop() {
+            Some(next_hand) => {
+                let (player, count) = self.hit_hand(&next_hand, deck, self.count)?;
+                let round_state = RoundState { player, count, stake: 1, waiting, hands, ..self.clone() };
+                round_state.stand_split_aces(deck)
+            }
+
+            None => RoundState { hands, ..self.clone() }.settle(deck)
+        };
+    }
+
+    /// the dealer plays, unless all of the player's hands are bust, and each hand is settled
+    fn settle(&self, deck: &mut Deck) -> Result<RoundState, RoundError> {
+        if self.hands.iter().all(|played| matches!(played.outcome, Lost)) {
+            //the hole card is turned over at the end of the round, without one the dealer only draws to settle the insurance
+            return if self.hole.is_some() || self.insurance == Insurance::Taken {
+                let (dealer, count) = self.reveal_hole(deck)?;
+                Ok(RoundState { outcome: Outcome::Lost, dealer, hole: None, count, ..self.clone() })
+            } else {
+                Ok(RoundState { outcome: Outcome::Lost, ..self.clone() })
+            };
+        }
+
+        let (dealer, count) = self.reveal_hole(deck)?;
+        //a dealer natural beats any player hand, including a 21 made of three or more cards.
+        //When the dealer peeks this can only happen with early surrender or no hole card, where doubles and splits are lost too
+        let dealer_natural = dealer.sum == 21;
+        let (dealer, count) = if !dealer_natural && RoundState::dealer_draws(&dealer, &self.rules) {
+            self.hit_dealer(&dealer, deck, count)?
+        } else {
+            (dealer, count)
+        };
+
+        let hands: Vec<PlayedHand> = self.hands.iter()
+            .map(|played| match played.outcome {
+                Playing if dealer_natural => PlayedHand { outcome: Outcome::Lost, ..played.clone() },
+                Playing => PlayedHand { outcome: RoundState::compare(&played.hand, &dealer), ..played.clone() },
+                _ => played.clone()
+            })
+            .collect();
+
+        let outcome = if hands.len() == 1 {
+            hands[0].outcome
         } else {
-            -1
+            //with split hands the round is won or lost according to the net units
+            let net: i32 = hands.iter()
+                .map(|played| match played.outcome {
+                    Won => played.stake as i32,
+                    Lost => -(played.stake as i32),
+                    _ => 0
+                })
+                .sum();
+            match net.cmp(&0) {
+                Ordering::Greater => Outcome::Won,
+                Ordering::Equal => Outcome::Draw,
+                Ordering::Less => Outcome::Lost
+            }
+        };
+
+        return Ok(RoundState { outcome, dealer, hole: None, count, hands, ..self.clone() });
+    }
+
+    fn compare(player: &Hand, dealer: &Hand) -> Outcome {
+        //compare the totals only, a hard 19 pushes against a soft 19
+        return match player.partial_cmp(dealer) {
+            Some(Ordering::Greater) => Outcome::Won,
+            Some(Ordering::Equal) => Outcome::Draw,
+            _ => Outcome::Lost
         };
     }
 
-    fn hit_player(player_hand: &Hand, deck: &mut Deck, hilo_acc: i32) -> (Hand, i32) {
-        let card = deal(deck);
-        (player_hand.hit(card), hilo_acc + RoundState::card_hilo(card))
+    /// returns true if the player won, either with a natural or not
+    pub fn won(&self) -> bool {
+        return matches!(self.outcome, Won | Blackjack);
+    }
+
+    pub fn blackjack(&self) -> bool {
+        return matches!(self.outcome, Blackjack);
+    }
+
+    pub fn lost(&self) -> bool {
+        return matches!(self.outcome, Lost);
+    }
+
+    pub fn surrendered(&self) -> bool {
+        return matches!(self.outcome, Surrendered);
+    }
+
+    pub fn draw(&self) -> bool {
+        return matches!(self.outcome, Draw);
+    }
+
+    pub fn finished(&self) -> bool {
+        return !matches!(self.outcome, Playing);
+    }
+
+    fn hit_hand(&self, hand: &Hand, deck: &mut Deck, count_acc: i32) -> Result<(Hand, i32), RoundError> {
+        let card = deal(deck)?;
+        Ok((hand.hit(card), count_acc + self.counting.tag(card)))
     }
 
-    fn hit_dealer(dealer_hand: &Hand, deck: &mut Deck, hilo_acc: i32) -> (Hand, i32) {
-        let card = deal(deck);
+    fn hit_dealer(&self, dealer_hand: &Hand, deck: &mut Deck, count_acc: i32) -> Result<(Hand, i32), RoundError> {
+        let card = deal(deck)?;
         //      println!("Card dealt to dealer: {:?}", card);
-        let new_card_hilo_acc = hilo_acc + RoundState::card_hilo(card);
+        let new_card_count_acc = count_acc + self.counting.tag(card);
         let new_dealer_hand = dealer_hand.hit(card);
 
-        return if new_dealer_hand.sum < 17 {
+        return if RoundState::dealer_draws(&new_dealer_hand, &self.rules) {
             //        println!("Dealer sum {:?}, still less than 17", new_dealer_hand.sum);
-            RoundState::hit_dealer(&new_dealer_hand, deck, new_card_hilo_acc)
+            self.hit_dealer(&new_dealer_hand, deck, new_card_count_acc)
         } else {
             //      println!("Dealer stays at sum {:?}", new_dealer_hand.sum);
-            (new_dealer_hand, new_card_hilo_acc)
+            Ok((new_dealer_hand, new_card_count_acc))
         };
     }
+
+    /// the dealer draws to 17, and also on a soft 17 if the rules say so
+    fn dealer_draws(dealer_hand: &Hand, rules: &TableRules) -> bool {
+        return dealer_hand.sum < 17 || (dealer_hand.sum == 17 && dealer_hand.ace && rules.dealer_hits_soft_17);
+    }
 }
 
-/// Assumes the deck has cards
-fn deal(deck: &mut Deck) -> u8 {
-    return deck.deal().unwrap();
+/// fails if the deck runs out of cards
+fn deal(deck: &mut Deck) -> Result<Card, RoundError> {
+    return deck.deal().ok_or(RoundError::OutOfCards);
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::counting::{KO, WongHalves};
     use crate::deck::Deck;
+    use crate::rules::{DoubleRule, HoleCard};
     use super::*;
 
+    #[test]
+    fn test_out_of_cards() {
+        let mut deck = Deck::new_rigged(&[10, 6]);
+        assert_eq!(RoundError::OutOfCards, RoundState::new(&mut deck, &TableRules::default()).unwrap_err());
+
+        //the dealer has to draw to 12 but the deck is exhausted
+        let mut deck = Deck::new_rigged(&[10, 6, 5, 7]);
+        let start = RoundState::new(&mut deck, &TableRules::default()).unwrap();
+        assert_eq!(RoundError::OutOfCards, start.stand(&mut deck).unwrap_err());
+        assert_eq!(RoundError::OutOfCards, start.hit(&mut deck).unwrap_err());
+    }
+
     #[test]
     fn test_losing_round() {
         let cards: [u8; 10] = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10];
         let mut deck = Deck::new_rigged(&cards);
 
-        let start = RoundState::new(&mut deck);
+        let start = RoundState::new(&mut deck, &TableRules::default()).unwrap();
         println!("Player got: {:?}", start.player);
         println!("Dealer got: {:?}", start.dealer);
 
@@ -174,7 +558,7 @@ mod tests {
         let cards: [u8; 10] = [10, 2, 3, 6, 9, 6, 7, 8, 9, 10];
         let mut deck = Deck::new_rigged(&cards);
 
-        let start = RoundState::new(&mut deck);
+        let start = RoundState::new(&mut deck, &TableRules::default()).unwrap();
         println!("Player got: {:?}", start.player);
         println!("Dealer got: {:?}", start.dealer);
 
@@ -205,10 +589,10 @@ mod tests {
 
     #[test]
     fn test_draw_round() {
-        let cards: [u8; 10] = [10, 2, 2, 6, 10, 6, 7, 8, 9, 10];
+        let cards: [u8; 10] = [10, 2, 2, 10, 6, 6, 7, 8, 9, 10];
         let mut deck = Deck::new_rigged(&cards);
 
-        let start = RoundState::new(&mut deck);
+        let start = RoundState::new(&mut deck, &TableRules::default()).unwrap();
         println!("Player got: {:?}", start.player);
         println!("Dealer got: {:?}", start.dealer);
 
@@ -236,10 +620,10 @@ mod tests {
 
     #[test]
     fn test_dealer_bust_round() {
-        let cards: [u8; 10] = [10, 2, 2, 6, 10, 3, 7, 8, 9, 10];
+        let cards: [u8; 10] = [10, 2, 2, 10, 6, 3, 7, 8, 9, 10];
         let mut deck = Deck::new_rigged(&cards);
 
-        let start = RoundState::new(&mut deck);
+        let start = RoundState::new(&mut deck, &TableRules::default()).unwrap();
         println!("Player got: {:?}", start.player);
         println!("Dealer got: {:?}", start.dealer);
 
@@ -268,10 +652,10 @@ mod tests {
 
     #[test]
     fn test_win_round() {
-        let cards: [u8; 10] = [10, 3, 2, 6, 10, 6, 7, 8, 9, 10];
+        let cards: [u8; 10] = [10, 3, 2, 10, 6, 6, 7, 8, 9, 10];
         let mut deck = Deck::new_rigged(&cards);
 
-        let start = RoundState::new(&mut deck);
+        let start = RoundState::new(&mut deck, &TableRules::default()).unwrap();
         println!("Player got: {:?}", start.player);
         println!("Dealer got: {:?}", start.dealer);
 
@@ -298,17 +682,596 @@ mod tests {
     }
 
     #[test]
-    fn test_card_hilo() {
-        assert_eq!(RoundState::card_hilo(2), 1);
-        assert_eq!(RoundState::card_hilo(3), 1);
-        assert_eq!(RoundState::card_hilo(4), 1);
-        assert_eq!(RoundState::card_hilo(5), 1);
-        assert_eq!(RoundState::card_hilo(6), 1);
-        assert_eq!(RoundState::card_hilo(7), 0);
-        assert_eq!(RoundState::card_hilo(8), 0);
-        assert_eq!(RoundState::card_hilo(9), 0);
-        assert_eq!(RoundState::card_hilo(10), -1);
-        assert_eq!(RoundState::card_hilo(1), -1);
+    fn test_round_keeps_rules() {
+        let cards: [u8; 10] = [10, 2, 2, 10, 6, 3, 7, 8, 9, 10];
+        let mut deck = Deck::new_rigged(&cards);
+        let rules = TableRules { decks: 6, dealer_hits_soft_17: true, ..TableRules::default() };
+
+        let start = RoundState::new(&mut deck, &rules).unwrap();
+        assert_eq!(start.rules, rules);
+
+        let after_hit = start.hit(&mut deck).unwrap();
+        assert_eq!(after_hit.rules, rules);
+
+        let after_stand = after_hit.stand(&mut deck).unwrap();
+        assert_eq!(after_stand.rules, rules);
+    }
+
+    fn h17() -> TableRules {
+        return TableRules { dealer_hits_soft_17: true, ..TableRules::default() };
+    }
+
+    #[test]
+    fn test_s17_dealer_stands_on_soft_17() {
+        let cards: [u8; 6] = [10, 9, 1, 6, 2, 10];
+        let mut deck = Deck::new_rigged(&cards);
+
+        let start = RoundState::new(&mut deck, &TableRules::default()).unwrap().insure(false, &mut deck).unwrap();
+        let after_stand = start.stand(&mut deck).unwrap();
+        println!("Round finished: {:?}", after_stand);
+
+        assert_eq!((after_stand.dealer.sum, after_stand.dealer.ace, after_stand.dealer.card_count()), (17, true, 2));
+        assert!(after_stand.won());
+    }
+
+    #[test]
+    fn test_h17_dealer_hits_soft_17() {
+        let cards: [u8; 6] = [10, 9, 1, 6, 2, 10];
+        let mut deck = Deck::new_rigged(&cards);
+
+        let start = RoundState::new(&mut deck, &h17()).unwrap().insure(false, &mut deck).unwrap();
+        let after_stand = start.stand(&mut deck).unwrap();
+        println!("Round finished: {:?}", after_stand);
+
+        assert_eq!((after_stand.dealer.sum, after_stand.dealer.ace, after_stand.dealer.card_count()), (19, true, 3));
+        assert!(after_stand.draw());
+    }
+
+    #[test]
+    fn test_h17_dealer_hits_soft_17_from_three_cards() {
+        let cards: [u8; 7] = [10, 8, 1, 2, 4, 3, 10];
+        let mut deck = Deck::new_rigged(&cards);
+
+        let s17_start = RoundState::new(&mut deck, &TableRules::default()).unwrap().insure(false, &mut deck).unwrap();
+        let s17_stand = s17_start.stand(&mut deck).unwrap();
+        assert_eq!((s17_stand.dealer.sum, s17_stand.dealer.ace, s17_stand.dealer.card_count()), (17, true, 3));
+        assert!(s17_stand.won());
+
+        let mut deck = Deck::new_rigged(&cards);
+        let h17_start = RoundState::new(&mut deck, &h17()).unwrap().insure(false, &mut deck).unwrap();
+        let h17_stand = h17_start.stand(&mut deck).unwrap();
+        assert_eq!((h17_stand.dealer.sum, h17_stand.dealer.ace, h17_stand.dealer.card_count()), (20, true, 4));
+        assert!(h17_stand.lost());
+    }
+
+    #[test]
+    fn test_h17_dealer_stands_on_hard_17() {
+        let cards: [u8; 6] = [10, 9, 10, 7, 2, 10];
+        let mut deck = Deck::new_rigged(&cards);
+
+        let start = RoundState::new(&mut deck, &h17()).unwrap();
+        let after_stand = start.stand(&mut deck).unwrap();
+        println!("Round finished: {:?}", after_stand);
+
+        assert_eq!((after_stand.dealer.sum, after_stand.dealer.ace, after_stand.dealer.card_count()), (17, false, 2));
+        assert!(after_stand.won());
+    }
+
+    #[test]
+    fn test_h17_dealer_stands_once_soft_17_turns_hard() {
+        let cards: [u8; 6] = [10, 8, 1, 6, 10, 4];
+        let mut deck = Deck::new_rigged(&cards);
+
+        let start = RoundState::new(&mut deck, &h17()).unwrap().insure(false, &mut deck).unwrap();
+        let after_stand = start.stand(&mut deck).unwrap();
+        println!("Round finished: {:?}", after_stand);
+
+        assert_eq!((after_stand.dealer.sum, after_stand.dealer.ace, after_stand.dealer.card_count()), (17, false, 3));
+        assert!(after_stand.won());
+    }
+
+    #[test]
+    fn test_player_blackjack() {
+        let cards: [u8; 6] = [1, 10, 10, 9, 2, 10];
+        let mut deck = Deck::new_rigged(&cards);
+
+        let start = RoundState::new(&mut deck, &TableRules::default()).unwrap();
+        println!("Round finished: {:?}", start);
+
+        assert!(start.finished());
+        assert!(start.blackjack());
+        assert!(start.won());
+        assert_eq!(start.dealer.sum, 19);
+    }
+
+    #[test]
+    fn test_player_and_dealer_blackjack_push() {
+        let cards: [u8; 6] = [10, 1, 1, 10, 2, 10];
+        let mut deck = Deck::new_rigged(&cards);
+
+        let start = RoundState::new(&mut deck, &TableRules::default()).unwrap().insure(false, &mut deck).unwrap();
+        println!("Round finished: {:?}", start);
+
+        assert!(start.finished());
+        assert!(start.draw());
+        assert!(!start.blackjack());
+    }
+
+    #[test]
+    fn test_dealer_blackjack_beats_three_card_21() {
+        let cards: [u8; 6] = [10, 5, 10, 6, 1, 10];
+        let mut deck = Deck::new_rigged(&cards);
+        //with a hole card the dealer would have peeked and ended the round straight away
+        let rules = TableRules { hole_card: HoleCard::NoHoleCard, ..TableRules::default() };
+
+        let start = RoundState::new(&mut deck, &rules).unwrap();
+        assert!(!start.finished());
+
+        let after_hit = start.hit(&mut deck).unwrap();
+        assert_eq!(after_hit.player.sum, 21);
+
+        let after_stand = after_hit.stand(&mut deck).unwrap();
+        println!("Round finished: {:?}", after_stand);
+
+        assert_eq!(after_stand.dealer.sum, 21);
+        assert!(after_stand.lost());
+    }
+
+    #[test]
+    fn test_double_round() {
+        let cards: [u8; 6] = [5, 6, 9, 10, 10, 8];
+        let mut deck = Deck::new_rigged(&cards);
+
+        let start = RoundState::new(&mut deck, &TableRules::default()).unwrap();
+        assert!(start.can_double());
+
+        let after_double = start.double(&mut deck).unwrap();
+        println!("Round finished: {:?}", after_double);
+
+        assert_eq!(after_double.player.sum, 21);
+        assert_eq!(after_double.player.card_count(), 3);
+        assert_eq!(after_double.stake, 2);
+        assert!(after_double.finished());
+        assert!(after_double.won());
+    }
+
+    #[test]
+    fn test_double_bust_round() {
+        let cards: [u8; 6] = [10, 3, 9, 10, 10, 8];
+        let mut deck = Deck::new_rigged(&cards);
+
+        let start = RoundState::new(&mut deck, &TableRules::default()).unwrap();
+        let after_double = start.double(&mut deck).unwrap();
+        println!("Round finished: {:?}", after_double);
+
+        assert!(after_double.player.is_bust());
+        //the hole card is turned over, but the dealer does not draw
+        assert_eq!(after_double.dealer.card_count(), 2);
+        assert_eq!(after_double.stake, 2);
+        assert!(after_double.lost());
+    }
+
+    #[test]
+    fn test_no_double_after_hit() {
+        let cards: [u8; 6] = [2, 3, 9, 4, 10, 8];
+        let mut deck = Deck::new_rigged(&cards);
+
+        let start = RoundState::new(&mut deck, &TableRules::default()).unwrap();
+        let after_hit = start.hit(&mut deck).unwrap();
+
+        assert!(!after_hit.can_double());
+        assert_eq!(RoundError::NotAllowed, after_hit.double(&mut deck).unwrap_err());
+    }
+
+    #[test]
+    fn test_double_restrictions() {
+        let nine_to_eleven = TableRules { double: DoubleRule::NineToEleven, ..TableRules::default() };
+        let ten_or_eleven = TableRules { double: DoubleRule::TenOrEleven, ..TableRules::default() };
+        let never = TableRules { double: DoubleRule::Never, ..TableRules::default() };
+
+        let can_double = |cards: [u8; 4], rules: &TableRules| {
+            let mut deck = Deck::new_rigged(&cards);
+            RoundState::new(&mut deck, rules).unwrap().can_double()
+        };
+
+        assert!(can_double([4, 5, 10, 8], &nine_to_eleven));
+        assert!(can_double([5, 6, 10, 8], &nine_to_eleven));
+        assert!(!can_double([4, 4, 10, 8], &nine_to_eleven));
+        assert!(!can_double([10, 2, 10, 8], &nine_to_eleven));
+        assert!(!can_double([1, 8, 10, 8], &nine_to_eleven));
+
+        assert!(!can_double([4, 5, 10, 8], &ten_or_eleven));
+        assert!(can_double([4, 6, 10, 8], &ten_or_eleven));
+
+        assert!(!can_double([5, 6, 10, 8], &never));
+        assert!(can_double([1, 7, 10, 8], &TableRules::default()));
+    }
+
+    #[test]
+    fn test_split_round() {
+        let cards: [u8; 8] = [8, 8, 10, 7, 3, 8, 6, 10];
+        let mut deck = Deck::new_rigged(&cards);
+
+        let start = RoundState::new(&mut deck, &TableRules::default()).unwrap();
+        assert!(start.can_split());
+
+        let after_split = start.split(&mut deck).unwrap();
+        assert_eq!(after_split.player.sum, 11);
+        assert_eq!(after_split.waiting.len(), 1);
+        assert!(after_split.can_double());
+        assert!(!after_split.can_split());
+
+        let after_double = after_split.double(&mut deck).unwrap();
+        assert!(!after_double.finished());
+        assert_eq!(after_double.hands.len(), 1);
+        assert_eq!(after_double.player.sum, 14);
+        assert_eq!(after_double.stake, 1);
+
+        let after_stand = after_double.stand(&mut deck).unwrap();
+        println!("Round finished: {:?}", after_stand);
+
+        assert!(after_stand.finished());
+        assert_eq!(after_stand.dealer.sum, 17);
+        assert_eq!(after_stand.hands.len(), 2);
+        assert!(matches!(after_stand.hands[0].outcome, Outcome::Won));
+        assert_eq!(after_stand.hands[0].stake, 2);
+        assert!(matches!(after_stand.hands[1].outcome, Outcome::Lost));
+        assert!(after_stand.won());
+    }
+
+    #[test]
+    fn test_resplit_round() {
+        let cards: [u8; 10] = [8, 8, 10, 7, 8, 10, 8, 10, 10, 10];
+        let rules = TableRules { max_splits: 2, ..TableRules::default() };
+        let mut deck = Deck::new_rigged(&cards);
+
+        let start = RoundState::new(&mut deck, &rules).unwrap();
+        let after_split = start.split(&mut deck).unwrap();
+        assert!(after_split.can_split());
+
+        let after_resplit = after_split.split(&mut deck).unwrap();
+        assert_eq!(after_resplit.player.sum, 18);
+        assert_eq!(after_resplit.waiting.len(), 2);
+
+        let after_stand = after_resplit.stand(&mut deck).unwrap();
+        //the third 8 gets another 8, but the limit of splits was reached
+        assert_eq!(after_stand.player.sum, 16);
+        assert!(!after_stand.can_split());
+
+        let after_stand = after_stand.stand(&mut deck).unwrap()
+            .stand(&mut deck).unwrap();
+        println!("Round finished: {:?}", after_stand);
+
+        assert!(after_stand.finished());
+        assert_eq!(after_stand.hands.len(), 3);
+        assert_eq!(after_stand.dealer.sum, 17);
+        assert!(matches!(after_stand.hands[0].outcome, Outcome::Won));
+        assert!(matches!(after_stand.hands[1].outcome, Outcome::Lost));
+        assert!(matches!(after_stand.hands[2].outcome, Outcome::Won));
+        assert!(after_stand.won());
+    }
+
+    #[test]
+    fn test_split_aces_get_one_card() {
+        let cards: [u8; 7] = [1, 1, 9, 8, 5, 10, 10];
+        let mut deck = Deck::new_rigged(&cards);
+
+        let start = RoundState::new(&mut deck, &TableRules::default()).unwrap();
+        let after_split = start.split(&mut deck).unwrap();
+        println!("Round finished: {:?}", after_split);
+
+        assert!(after_split.finished());
+        assert_eq!(after_split.hands[0].hand.sum, 16);
+        assert_eq!(after_split.hands[1].hand.sum, 21);
+        assert_eq!(after_split.dealer.sum, 17);
+        assert!(matches!(after_split.hands[0].outcome, Outcome::Lost));
+        //21 on a split ace is not a blackjack
+        assert!(matches!(after_split.hands[1].outcome, Outcome::Won));
+        assert!(after_split.draw());
+    }
+
+    #[test]
+    fn test_resplit_aces() {
+        let cards: [u8; 8] = [1, 1, 9, 8, 1, 10, 10, 10];
+        let rules = TableRules { resplit_aces: true, ..TableRules::default() };
+        let mut deck = Deck::new_rigged(&cards);
+
+        let start = RoundState::new(&mut deck, &rules).unwrap();
+        let after_split = start.split(&mut deck).unwrap();
+
+        assert!(!after_split.finished());
+        assert!(after_split.can_split());
+        assert!(!after_split.can_hit());
+        assert!(!after_split.can_double());
+
+        let mut deck = Deck::new_rigged(&cards);
+        let start = RoundState::new(&mut deck, &TableRules::default()).unwrap();
+        let after_split = start.split(&mut deck).unwrap();
+
+        assert!(after_split.finished());
+        assert_eq!(after_split.hands.len(), 2);
+        assert_eq!(after_split.hands[0].hand.sum, 12);
+    }
+
+    #[test]
+    fn test_no_double_after_split() {
+        let cards: [u8; 6] = [5, 5, 10, 8, 6, 10];
+        let rules = TableRules { double_after_split: false, ..TableRules::default() };
+        let mut deck = Deck::new_rigged(&cards);
+
+        let start = RoundState::new(&mut deck, &rules).unwrap();
+        assert!(start.can_double());
+
+        let after_split = start.split(&mut deck).unwrap();
+        assert_eq!(after_split.player.sum, 11);
+        assert!(!after_split.can_double());
+    }
+
+    #[test]
+    fn test_no_split_allowed() {
+        let cards: [u8; 6] = [5, 5, 10, 6, 10, 8];
+        let rules = TableRules { max_splits: 0, ..TableRules::default() };
+        let mut deck = Deck::new_rigged(&cards);
+
+        let start = RoundState::new(&mut deck, &rules).unwrap();
+        assert!(!start.can_split());
+        assert_eq!(RoundError::NotAllowed, start.split(&mut deck).unwrap_err());
+    }
+
+    #[test]
+    fn test_split_hands_all_bust() {
+        let cards: [u8; 8] = [8, 8, 10, 7, 6, 10, 6, 10];
+        let mut deck = Deck::new_rigged(&cards);
+
+        let start = RoundState::new(&mut deck, &TableRules::default()).unwrap();
+        let after_hits = start.split(&mut deck).unwrap()
+            .hit(&mut deck).unwrap()
+            .hit(&mut deck).unwrap();
+        println!("Round finished: {:?}", after_hits);
+
+        assert!(after_hits.finished());
+        assert!(after_hits.lost());
+        //the hole card is turned over, but the dealer does not draw
+        assert_eq!(after_hits.dealer.card_count(), 2);
+    }
+
+    #[test]
+    fn test_no_surrender_offered() {
+        let cards: [u8; 6] = [10, 6, 10, 7, 10, 8];
+        let mut deck = Deck::new_rigged(&cards);
+
+        let start = RoundState::new(&mut deck, &TableRules::default()).unwrap();
+        assert!(!start.can_surrender());
+        assert_eq!(RoundError::NotAllowed, start.surrender(&mut deck).unwrap_err());
+    }
+
+    #[test]
+    fn test_late_surrender() {
+        let cards: [u8; 6] = [10, 6, 10, 7, 10, 8];
+        let rules = TableRules { surrender: Surrender::Late, ..TableRules::default() };
+        let mut deck = Deck::new_rigged(&cards);
+
+        let start = RoundState::new(&mut deck, &rules).unwrap();
+        assert!(start.can_surrender());
+
+        let after_surrender = start.surrender(&mut deck).unwrap();
+        println!("Round finished: {:?}", after_surrender);
+
+        assert!(after_surrender.finished());
+        assert!(after_surrender.surrendered());
+        assert!(!after_surrender.lost());
+    }
+
+    #[test]
+    fn test_late_surrender_against_dealer_blackjack() {
+        let cards: [u8; 6] = [10, 6, 1, 10, 10, 8];
+        let rules = TableRules { surrender: Surrender::Late, ..TableRules::default() };
+        let mut deck = Deck::new_rigged(&cards);
+
+        //the dealer peeks before late surrender is offered
+        let start = RoundState::new(&mut deck, &rules).unwrap().insure(false, &mut deck).unwrap();
+        assert!(start.lost());
+        assert!(!start.can_surrender());
+
+        let rules = TableRules { surrender: Surrender::Late, hole_card: HoleCard::NoHoleCard, ..TableRules::default() };
+        let mut deck = Deck::new_rigged(&cards);
+
+        let after_surrender = RoundState::new(&mut deck, &rules).unwrap().insure(false, &mut deck).unwrap().surrender(&mut deck).unwrap();
+        println!("Round finished: {:?}", after_surrender);
+
+        assert!(after_surrender.lost());
+
+        let rules = TableRules { surrender: Surrender::Early, ..TableRules::default() };
+        let mut deck = Deck::new_rigged(&cards);
+
+        let after_surrender = RoundState::new(&mut deck, &rules).unwrap().insure(false, &mut deck).unwrap().surrender(&mut deck).unwrap();
+        println!("Round finished: {:?}", after_surrender);
+
+        assert!(after_surrender.surrendered());
+    }
+
+    #[test]
+    fn test_no_surrender_after_hit() {
+        let cards: [u8; 6] = [2, 3, 10, 4, 10, 8];
+        let rules = TableRules { surrender: Surrender::Early, ..TableRules::default() };
+        let mut deck = Deck::new_rigged(&cards);
+
+        let after_hit = RoundState::new(&mut deck, &rules).unwrap().hit(&mut deck).unwrap();
+        assert!(!after_hit.can_surrender());
+    }
+
+    #[test]
+    fn test_insurance_offered_on_ace() {
+        let cards: [u8; 6] = [10, 6, 1, 10, 10, 8];
+        let mut deck = Deck::new_rigged(&cards);
+
+        let start = RoundState::new(&mut deck, &TableRules::default()).unwrap();
+        assert_eq!(start.insurance, Insurance::Offered);
+        assert!(!start.finished());
+        assert!(!start.can_hit());
+        assert_eq!(RoundError::NotAllowed, start.hit(&mut deck).unwrap_err());
+        assert_eq!(RoundError::NotAllowed, start.stand(&mut deck).unwrap_err());
+
+        //the dealer peeks after the insurance is taken, and the round ends on the natural
+        let after_insurance = start.insure(true, &mut deck).unwrap();
+        assert_eq!(after_insurance.insurance, Insurance::Taken);
+        assert_eq!(RoundError::NotAllowed, after_insurance.insure(true, &mut deck).unwrap_err());
+        assert!(after_insurance.finished());
+        assert!(after_insurance.lost());
+        assert!(after_insurance.insurance_won());
+    }
+
+    #[test]
+    fn test_insurance_not_offered() {
+        let cards: [u8; 6] = [10, 6, 10, 1, 10, 8];
+        let mut deck = Deck::new_rigged(&cards);
+
+        let start = RoundState::new(&mut deck, &TableRules::default()).unwrap();
+        assert_eq!(start.insurance, Insurance::NotOffered);
+        assert_eq!(RoundError::NotAllowed, start.insure(true, &mut deck).unwrap_err());
+    }
+
+    #[test]
+    fn test_insurance_settled_when_player_busts() {
+        let cards: [u8; 6] = [10, 6, 1, 8, 9, 10];
+        let mut deck = Deck::new_rigged(&cards);
+
+        let after_hit = RoundState::new(&mut deck, &TableRules::default()).unwrap()
+            .insure(true, &mut deck).unwrap()
+            .hit(&mut deck).unwrap();
+        println!("Round finished: {:?}", after_hit);
+
+        assert!(after_hit.lost());
+        assert_eq!(after_hit.dealer.card_count(), 2);
+        assert!(!after_hit.insurance_won());
+    }
+
+    #[test]
+    fn test_hole_card_hidden_until_revealed() {
+        let cards: [u8; 6] = [10, 7, 9, 2, 8, 10];
+        let mut deck = Deck::new_rigged(&cards);
+
+        let start = RoundState::new(&mut deck, &TableRules::default()).unwrap();
+        assert_eq!(start.dealer.sum, 9);
+        assert_eq!(start.dealer.card_count(), 1);
+        assert_eq!(start.count, -1);
+
+        let after_stand = start.stand(&mut deck).unwrap();
+        println!("Round finished: {:?}", after_stand);
+
+        assert_eq!(after_stand.dealer.sum, 19);
+        assert_eq!(after_stand.count, 0);
+        assert!(after_stand.lost());
+    }
+
+    #[test]
+    fn test_peek_ends_round_on_dealer_blackjack() {
+        let cards: [u8; 6] = [9, 2, 10, 1, 10, 10];
+        let mut deck = Deck::new_rigged(&cards);
+
+        let start = RoundState::new(&mut deck, &TableRules::default()).unwrap();
+        println!("Round finished: {:?}", start);
+
+        assert!(start.finished());
+        assert!(start.lost());
+        assert_eq!(start.dealer.sum, 21);
+        assert!(!start.can_double());
+        assert_eq!(deck.len(), 2);
+    }
+
+    #[test]
+    fn test_no_hole_card_loses_double_to_dealer_blackjack() {
+        let cards: [u8; 6] = [9, 2, 10, 9, 1, 10];
+        let rules = TableRules { hole_card: HoleCard::NoHoleCard, ..TableRules::default() };
+        let mut deck = Deck::new_rigged(&cards);
+
+        let start = RoundState::new(&mut deck, &rules).unwrap();
+        assert!(!start.finished());
+
+        let after_double = start.double(&mut deck).unwrap();
+        println!("Round finished: {:?}", after_double);
+
+        assert_eq!(after_double.player.sum, 20);
+        assert_eq!(after_double.dealer.sum, 21);
+        assert_eq!(after_double.stake, 2);
+        assert!(after_double.lost());
+    }
+
+    #[test]
+    fn test_early_surrender_peeks_before_double() {
+        let cards: [u8; 6] = [9, 2, 10, 1, 9, 10];
+        let rules = TableRules { surrender: Surrender::Early, ..TableRules::default() };
+        let mut deck = Deck::new_rigged(&cards);
+
+        let start = RoundState::new(&mut deck, &rules).unwrap();
+        assert!(!start.finished());
+        assert!(start.can_surrender());
+
+        //the dealer has a natural, so the double is never placed
+        let after_double = start.double(&mut deck).unwrap();
+        println!("Round finished: {:?}", after_double);
+
+        assert_eq!(after_double.player.sum, 11);
+        assert_eq!(after_double.stake, 1);
+        assert!(after_double.lost());
+    }
+
+    #[test]
+    fn test_unseen_cards_and_true_count() {
+        let mut cards = vec![6, 5, 4, 10, 3];
+        cards.extend([9; 100]);
+        let mut deck = Deck::new_rigged(&cards);
+        let start = RoundState::new(&mut deck, &TableRules::default()).unwrap();
+        //the hole card is not seen yet
+        assert_eq!(102, start.cards_remaining());
+        assert_eq!(3, start.count);
+        assert_eq!(1, start.true_count());
+
+        let after_hit = start.hit(&mut deck).unwrap();
+        assert_eq!(101, after_hit.cards_remaining());
+        assert_eq!(4, after_hit.count);
+        assert_eq!(2, after_hit.true_count());
+
+        let mut deck = Deck::new_rigged(&[10, 10, 10, 10, 10]);
+        let start = RoundState::new(&mut deck, &TableRules::default()).unwrap();
+        assert_eq!(-3, start.count);
+        assert_eq!(-6, start.true_count());
+        assert_eq!(2, start.cards_remaining());
+    }
+
+    #[test]
+    fn test_true_count_rounding() {
+        //three tens are seen with 100 cards left to see, a true count of -1.56
+        let mut cards = vec![10, 10, 10, 2];
+        cards.extend([9; 99]);
+        let mut deck = Deck::new_rigged(&cards);
+        let start = RoundState::new(&mut deck, &TableRules::default()).unwrap();
+        assert_eq!(100, start.cards_remaining());
+        assert!((start.decks_remaining() - 1.923).abs() < 0.001);
+        assert_eq!(-2, start.true_count());
+        assert_eq!(-2, start.true_count_rounded(Rounding::Floor));
+        assert_eq!(-1, start.true_count_rounded(Rounding::Truncate));
+        assert_eq!(-2, start.true_count_rounded(Rounding::HalfDeck));
+    }
+
+    #[test]
+    fn test_other_counting_systems() {
+        let mut cards = vec![5, 7, 4, 10, 3];
+        cards.extend([9; 100]);
+
+        //Wong Halves counts a 5 as 1.5, a 7 and a 4 as 0.5 and 1, doubled into 3, 1 and 2
+        let mut deck = Deck::new_rigged(&cards);
+        let start = RoundState::new_with_count(&mut deck, &TableRules::default(), &WongHalves, 0).unwrap();
+        assert_eq!(6, start.count);
+        assert_eq!(1, start.true_count());
+
+        //KO also counts the 7 and is not divided by the decks left
+        let mut deck = Deck::new_rigged(&cards);
+        let start = RoundState::new_with_count(&mut deck, &TableRules::default(), &KO, -4).unwrap();
+        assert_eq!(-1, start.count);
+        assert_eq!(-1, start.true_count());
+        let after_hit = start.hit(&mut deck).unwrap();
+        assert_eq!(0, after_hit.count);
     }
 
     #[test]
@@ -316,31 +1279,31 @@ mod tests {
         let cards: [u8; 10] = [10, 9, 6, 2, 10, 6, 7, 8, 9, 10];
         let mut deck = Deck::new_rigged(&cards);
 
-        let start = RoundState::new(&mut deck);
+        let start = RoundState::new(&mut deck, &TableRules::default()).unwrap();
         println!("Player got: {:?}", start.player);
         println!("Dealer got: {:?}", start.dealer);
 
-        assert_eq!(start.hilo, 0);
+        assert_eq!(start.count, 0);
     }
 
     #[test]
     fn test_hilo_counting_lo() {
-        let cards: [u8; 10] = [10, 10, 10, 1, 10, 1, 10, 10, 8, 9];
+        let cards: [u8; 10] = [10, 10, 10, 10, 1, 1, 10, 10, 8, 9];
         let mut deck = Deck::new_rigged(&cards);
 
-        let start = RoundState::new(&mut deck);
+        let start = RoundState::new(&mut deck, &TableRules::default()).unwrap();
         println!("Player got: {:?}", start.player);
         println!("Dealer got: {:?}", start.dealer);
 
-        assert_eq!(start.hilo, -3);
+        assert_eq!(start.count, -3);
 
         let after_hit = start.hit(&mut deck).unwrap();
         println!("After hit: {:?}", after_hit);
-        assert_eq!(after_hit.hilo, -4);
+        assert_eq!(after_hit.count, -4);
 
         let after_stand = after_hit.stand(&mut deck).unwrap();
         println!("End of round: {:?}", after_stand);
-        assert_eq!(after_stand.hilo, -5);
+        assert_eq!(after_stand.count, -5);
     }
 
     #[test]
@@ -348,19 +1311,19 @@ mod tests {
         let cards: [u8; 10] = [2, 2, 3, 4, 5, 6, 5, 4, 3, 2];
         let mut deck = Deck::new_rigged(&cards);
 
-        let start = RoundState::new(&mut deck);
+        let start = RoundState::new(&mut deck, &TableRules::default()).unwrap();
         println!("Player got: {:?}", start.player);
         println!("Dealer got: {:?}", start.dealer);
 
-        assert_eq!(start.hilo, 3);
+        assert_eq!(start.count, 3);
 
         let after_hit = start.hit(&mut deck).unwrap();
         println!("After hit: {:?}", after_hit);
-        assert_eq!(after_hit.hilo, 4);
+        assert_eq!(after_hit.count, 4);
 
         let after_stand = after_hit.stand(&mut deck).unwrap();
         println!("End of round: {:?}", after_stand);
-        assert_eq!(after_stand.hilo, 7);
+        assert_eq!(after_stand.count, 7);
     }
 
     #[test]
@@ -368,19 +1331,19 @@ mod tests {
         let cards: [u8; 10] = [7, 7, 7, 7, 7, 8, 8, 7, 7, 9];
         let mut deck = Deck::new_rigged(&cards);
 
-        let start = RoundState::new(&mut deck);
+        let start = RoundState::new(&mut deck, &TableRules::default()).unwrap();
         println!("Player got: {:?}", start.player);
         println!("Dealer got: {:?}", start.dealer);
 
-        assert_eq!(start.hilo, 0);
+        assert_eq!(start.count, 0);
 
         let after_hit = start.hit(&mut deck).unwrap();
         println!("After hit: {:?}", after_hit);
-        assert_eq!(after_hit.hilo, 0);
+        assert_eq!(after_hit.count, 0);
 
         let after_stand = after_hit.stand(&mut deck).unwrap();
         println!("End of round: {:?}", after_stand);
-        assert_eq!(after_stand.hilo, 0);
+        assert_eq!(after_stand.count, 0);
     }
 }
 
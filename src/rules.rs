@@ -0,0 +1,103 @@
+/// Which two-card hands the player is allowed to double down on.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DoubleRule {
+    /// doubling is not offered
+    Never,
+    /// double on any first two cards
+    AnyTwo,
+    /// double only on a hard 9, 10 or 11
+    NineToEleven,
+    /// double only on a hard 10 or 11
+    TenOrEleven,
+}
+
+impl DoubleRule {
+    /// returns true if a two-card hand of the total can be doubled
+    pub fn allows(&self, total: u8, soft: bool) -> bool {
+        return match self {
+            DoubleRule::Never => false,
+            DoubleRule::AnyTwo => true,
+            DoubleRule::NineToEleven => !soft && (9..=11).contains(&total),
+            DoubleRule::TenOrEleven => !soft && (10..=11).contains(&total),
+        };
+    }
+}
+
+/// Whether, and when, the player may surrender half of the stake instead of playing the hand.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Surrender {
+    /// surrender is not offered
+    None,
+    /// surrender is only offered after the dealer has checked for blackjack
+    Late,
+    /// surrender is offered before the dealer checks for blackjack
+    Early,
+}
+
+/// How the dealer's hole card is handled.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum HoleCard {
+    /// American style: the dealer peeks at the hole card and the round ends immediately on a dealer blackjack
+    Peek,
+    /// European style (ENHC): the dealer only draws a second card once the player has finished,
+    /// so doubles and splits are lost in full to a dealer blackjack
+    NoHoleCard,
+}
+
+/// How the cards are brought back into play.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Shuffling {
+    /// the shoe is dealt down to the cut card and then reshuffled
+    CutCard,
+    /// a continuous shuffling machine (CSM): the cards of every round go straight back into the shoe
+    Continuous,
+}
+
+/// The rules of the table a round is played under, so that agents can be trained and compared
+/// for the rule sets of different casinos.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TableRules {
+    /// the dealer draws on a soft 17 (H17) instead of standing on all 17s (S17)
+    pub dealer_hits_soft_17: bool,
+    /// how many 52 card decks are shuffled together
+    pub decks: u8,
+    /// the fraction of the shoe dealt before the cut card comes out and the shoe is reshuffled
+    pub penetration: f64,
+    /// whether the shoe is dealt to the cut card or continuously shuffled, in which case penetration does not apply
+    pub shuffling: Shuffling,
+    /// what a natural blackjack pays for every unit staked (1.5 for 3:2, 1.2 for 6:5)
+    pub blackjack_payout: f64,
+    /// which hands can be doubled
+    pub double: DoubleRule,
+    /// whether a hand that was split can still be doubled
+    pub double_after_split: bool,
+    /// how many times the player may split in a single round (0 disables splitting)
+    pub max_splits: u8,
+    /// whether split aces can be split again
+    pub resplit_aces: bool,
+    /// whether surrender is offered
+    pub surrender: Surrender,
+    /// how the dealer's hole card is handled
+    pub hole_card: HoleCard,
+}
+
+impl Default for TableRules {
+    /// A single deck table, reshuffled after three quarters have been dealt, where the dealer stands on soft 17, blackjack pays 3:2,
+    /// any two cards can be doubled (also after a split), up to four hands can be split,
+    /// there is no surrender and the dealer peeks for blackjack.
+    fn default() -> TableRules {
+        return TableRules {
+            dealer_hits_soft_17: false,
+            decks: 1,
+            penetration: 0.75,
+            shuffling: Shuffling::CutCard,
+            blackjack_payout: 1.5,
+            double: DoubleRule::AnyTwo,
+            double_after_split: true,
+            max_splits: 3,
+            resplit_aces: false,
+            surrender: Surrender::None,
+            hole_card: HoleCard::Peek,
+        };
+    }
+}
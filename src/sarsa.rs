@@ -1,45 +1,84 @@
 use std::collections::{VecDeque};
-use crate::blackjack_agent::{BlackjackAction, BlackjackState, EpisodeResult, reward};
-use crate::blackjack_policy::{e_greedy_policy, greedy_policy};
-use crate::deck::Deck;
+use crate::betting::{learn_bets, BetAction, BetState};
+use crate::blackjack_agent::{act, available_actions, BlackjackAction, BlackjackState, EpisodeResult, Features, reward};
+use crate::blackjack_policy::{e_greedy_policy, e_greedy_probabilities, greedy_policy};
+use crate::insurance::{insurance_episode, learn_episode_insurance, InsuranceAction, InsuranceState};
 use crate::qtable::{QTable, StateAction};
-use crate::round::{RoundState};
-use crate::learner::Learner;
+use crate::double_q::{double_q_target, updated_table};
+use crate::learner::{print_learning_curves, Learner};
+use crate::rules::TableRules;
+use crate::shoe::Shoe;
 
-pub enum Mode {
+pub enum Mode<'a> {
     SARSA,
-    SARSAMAX //a.k.a Q-Learning
+    SARSAMAX, //a.k.a Q-Learning
+    /// the expected value of the next state under the e-greedy policy, rather than of the action it happened to choose
+    ExpectedSARSA,
+    /// Double Q-learning, with the two estimates that take turns being updated, while the agent acts on their mean
+    DoubleQ(&'a mut [QTable<BlackjackState, BlackjackAction>; 2])
 }
 
-pub fn sarsa() -> Learner {
+pub fn sarsa(rules: TableRules, features: Features) -> Learner {
   println!("Running in SARSA mode");
-  Learner::new_trained(evaluate_episode_sarsa)
+  let mut shoe = Shoe::from(&rules);
+  shoe.set_counting(features.counting);
+  Learner::new_trained(|q_table, insurance_table, bet_table, episode_number| evaluate_episode_sarsa(q_table, insurance_table, bet_table, &mut shoe, &rules, &features, episode_number))
 }
 
-pub fn sarsamax() -> Learner {
+pub fn sarsamax(rules: TableRules, features: Features) -> Learner {
     println!("Running in SARSAMAX (Q-Learning) mode");
-    Learner::new_trained(evaluate_episode_sarsamax)
+    let mut shoe = Shoe::from(&rules);
+    shoe.set_counting(features.counting);
+    Learner::new_trained(|q_table, insurance_table, bet_table, episode_number| evaluate_episode_sarsamax(q_table, insurance_table, bet_table, &mut shoe, &rules, &features, episode_number))
 }
 
-pub fn evaluate_episode_sarsa(q_table: &mut QTable<BlackjackState, BlackjackAction>, episode_number: usize) -> (i32, f64) {
-    let mut deck = Deck::new_shuffled();
-    let (result, error) = episode(&mut deck, q_table, episode_number, Mode::SARSA);
-    (result.reward, error)
+pub fn expected_sarsa(rules: TableRules, features: Features) -> Learner {
+    println!("Running in Expected SARSA mode");
+    let mut shoe = Shoe::from(&rules);
+    shoe.set_counting(features.counting);
+    Learner::new_trained(|q_table, insurance_table, bet_table, episode_number| evaluate_episode_expected_sarsa(q_table, insurance_table, bet_table, &mut shoe, &rules, &features, episode_number))
 }
 
-pub fn evaluate_episode_sarsamax(q_table: &mut QTable<BlackjackState, BlackjackAction>, episode_number: usize) -> (i32, f64) {
-    let mut deck = Deck::new_shuffled();
-    let (result, error) = episode(&mut deck, q_table, episode_number, Mode::SARSAMAX);
-    (result.reward, error)
+/// learns with each of the modes and prints how their win rates and errors evolve side by side
+pub fn compare_modes(rules: TableRules, features: Features) {
+    let sarsa = sarsa(rules, features);
+    let sarsamax = sarsamax(rules, features);
+    let expected_sarsa = expected_sarsa(rules, features);
+    print_learning_curves(&[("SARSA", &sarsa), ("SARSAMAX", &sarsamax), ("Expected SARSA", &expected_sarsa)]);
 }
 
-pub fn episode(deck: &mut Deck, q_table: &mut QTable<BlackjackState, BlackjackAction>, episode_number: usize, mode: Mode ) -> (EpisodeResult, f64) {
+pub fn evaluate_episode_sarsa(q_table: &mut QTable<BlackjackState, BlackjackAction>, insurance_table: &mut QTable<InsuranceState, InsuranceAction>,
+                              bet_table: &mut QTable<BetState, BetAction>, shoe: &mut Shoe, rules: &TableRules, features: &Features, episode_number: usize) -> (f64, f64) {
+    let (result, error) = episode(shoe, rules, features, q_table, insurance_table, episode_number, Mode::SARSA);
+    learn_bets(bet_table, &result.bet, result.round_reward());
+    (result.round_reward(), error)
+}
+
+pub fn evaluate_episode_sarsamax(q_table: &mut QTable<BlackjackState, BlackjackAction>, insurance_table: &mut QTable<InsuranceState, InsuranceAction>,
+                                 bet_table: &mut QTable<BetState, BetAction>, shoe: &mut Shoe, rules: &TableRules, features: &Features, episode_number: usize) -> (f64, f64) {
+    let (result, error) = episode(shoe, rules, features, q_table, insurance_table, episode_number, Mode::SARSAMAX);
+    learn_bets(bet_table, &result.bet, result.round_reward());
+    (result.round_reward(), error)
+}
+
+pub fn evaluate_episode_expected_sarsa(q_table: &mut QTable<BlackjackState, BlackjackAction>, insurance_table: &mut QTable<InsuranceState, InsuranceAction>,
+                                       bet_table: &mut QTable<BetState, BetAction>, shoe: &mut Shoe, rules: &TableRules, features: &Features, episode_number: usize) -> (f64, f64) {
+    let (result, error) = episode(shoe, rules, features, q_table, insurance_table, episode_number, Mode::ExpectedSARSA);
+    learn_bets(bet_table, &result.bet, result.round_reward());
+    (result.round_reward(), error)
+}
+
+pub fn episode(shoe: &mut Shoe, rules: &TableRules, features: &Features, q_table: &mut QTable<BlackjackState, BlackjackAction>,
+               insurance_table: &mut QTable<InsuranceState, InsuranceAction>, episode_number: usize, mut mode: Mode) -> (EpisodeResult, f64) {
 
     let mut state_actions: VecDeque<StateAction<BlackjackState, BlackjackAction>> = VecDeque::new();
 
-    let mut round_state = RoundState::new(deck);
-    let mut agent_state = BlackjackState::from(&round_state);
-    let mut action = e_greedy_policy(&agent_state, q_table, episode_number);
+    //the bet is placed on the count before the round is dealt
+    shoe.shuffle_if_due();
+    let bet = BetState::from(shoe, features);
+    let (mut round_state, insurance) = insurance_episode(shoe.new_round(rules).unwrap(), shoe.deck(), features, insurance_table, episode_number);
+    let mut agent_state = BlackjackState::from_features(&round_state, features);
+    let mut action = e_greedy_policy(&agent_state, &available_actions(&round_state), q_table, episode_number);
     let mut state_action = StateAction{ agent_state, action };
     let mut sum_error = 0.0;
     let mut state_action_count = 0;
@@ -47,61 +86,69 @@ pub fn episode(deck: &mut Deck, q_table: &mut QTable<BlackjackState, BlackjackAc
     while !round_state.finished() {
         //apply the action
         state_actions.push_front(state_action);
-        let new_round_state = match action {
-            BlackjackAction::Hit => round_state.hit(deck).unwrap(),
-            BlackjackAction::Stand => round_state.stand(deck).unwrap()
-        };
-
-        if round_state.player.sum >= 12 && round_state.player.sum <= 20 {
-            //todo: this could do with some refactoring!
-            let reward = reward(&new_round_state) as f64;
-
-            let q_next = if !new_round_state.finished() {
-                //choose the next action according to the policy
-                let new_agent_state = BlackjackState::from(&new_round_state);
-                let next_action = e_greedy_policy(&new_agent_state, q_table, episode_number);
-                let next_state_action = StateAction{agent_state: new_agent_state, action: next_action };
-
-                let q = match mode {
-                    Mode::SARSA => {
-                        q_table.get_value(&next_state_action)
-                    }
-
-                    Mode::SARSAMAX => {
-                        //a.k.a q-learning
-                        let best_action = greedy_policy(&agent_state, q_table, episode_number);
-                        q_table.get_value(&StateAction{agent_state: new_agent_state, action: best_action })
-                    }
-                };
-
-                action = next_action;
-                agent_state = new_agent_state;
-                q
-            } else {
-                //q for terminal state is 0
-                0.0
+        let new_round_state = act(&round_state, action, shoe.deck()).unwrap();
+
+        //todo: this could do with some refactoring!
+        let reward = reward(&new_round_state);
+
+        //states with a single available action are also updated, since their values are bootstrapped on
+        let q_next = if !new_round_state.finished() {
+            //choose the next action according to the policy
+            let new_agent_state = BlackjackState::from_features(&new_round_state, features);
+            let actions = available_actions(&new_round_state);
+            let next_action = e_greedy_policy(&new_agent_state, &actions, q_table, episode_number);
+            let next_state_action = StateAction{agent_state: new_agent_state, action: next_action };
+
+            let q = match &mode {
+                Mode::SARSA => {
+                    q_table.get_value(&next_state_action)
+                }
+
+                Mode::SARSAMAX => {
+                    //a.k.a q-learning
+                    let best_action = greedy_policy(&new_agent_state, &actions, q_table, episode_number);
+                    q_table.get_value(&StateAction{agent_state: new_agent_state, action: best_action })
+                }
+
+                Mode::ExpectedSARSA => {
+                    e_greedy_probabilities(&new_agent_state, &actions, q_table, episode_number).iter()
+                        .map(|(action, probability)| probability * q_table.get_value(&StateAction{agent_state: new_agent_state, action: *action }))
+                        .sum()
+                }
+
+                Mode::DoubleQ(tables) => {
+                    double_q_target(tables, &new_agent_state, &actions, episode_number)
+                }
             };
 
-            let count = q_table.get_count(&state_action);
-            let q_value = q_table.get_value(&state_action);
-            let step_size = 1.0 / (count + 1) as f64;
+            action = next_action;
+            agent_state = new_agent_state;
+            q
+        } else {
+            //q for terminal state is 0
+            0.0
+        };
 
-            let error = reward + q_next - q_value;
-            sum_error = sum_error + f64::abs(error);
-            state_action_count += 1;
+        //double q-learning updates one of its estimates, the other modes the table the agent acts on
+        let table = match &mut mode {
+            Mode::DoubleQ(tables) => &mut tables[updated_table(episode_number)],
+            _ => &mut *q_table
+        };
+        let count = table.get_count(&state_action);
+        let q_value = table.get_value(&state_action);
+        let step_size = 1.0 / (count + 1) as f64;
 
-            let new_q_value = q_value + (step_size * error);
-            if new_q_value != q_value {
+        let error = reward + q_next - q_value;
+        sum_error += f64::abs(error);
+        state_action_count += 1;
 
-                q_table.update_value(&state_action, new_q_value);
-            }
+        let new_q_value = q_value + (step_size * error);
+        if new_q_value != q_value {
 
-        } else if !round_state.finished() {
-            //we had an obvious action no need to update q_values
-
-            //compute the new state, and find what the next action should be
-            agent_state = BlackjackState::from(&new_round_state);
-            action = e_greedy_policy(&agent_state, q_table, episode_number);
+            table.update_value(&state_action, new_q_value);
+            if let Mode::DoubleQ(tables) = &mode {
+                q_table.update_value(&state_action, (tables[0].get_value(&state_action) + tables[1].get_value(&state_action)) / 2.0);
+            }
         }
 
         state_action = StateAction{agent_state, action};
@@ -115,7 +162,11 @@ pub fn episode(deck: &mut Deck, q_table: &mut QTable<BlackjackState, BlackjackAc
         sum_error / state_action_count as f64
     };
 
-    return (EpisodeResult::from(&round_state, state_actions), mean_error);
+    shoe.end_round(&round_state);
+    let result = EpisodeResult::from(&round_state, state_actions, insurance, bet);
+    learn_episode_insurance(insurance_table, &result);
+
+    return (result, mean_error);
 }
 
 
@@ -0,0 +1,268 @@
+use crate::counting::{CountingSystem, HiLo, Rounding};
+use crate::deck::{Deck, Exhaustion};
+use crate::round::{RoundError, RoundState};
+use crate::rules::{Shuffling, TableRules};
+
+/// A shoe of several decks shuffled together that is dealt from over many rounds,
+/// so that the running count carries over from one round to the next.
+/// Once the cut card is reached the shoe is reshuffled and the count starts again from the initial count of the system.
+/// Should a round run out of cards, the discards are shuffled back in so that play can go on.
+/// A continuously shuffled shoe puts the cards of every round straight back in, so counting gains nothing.
+#[derive(Debug, Clone)]
+pub struct Shoe {
+    deck: Deck,
+    decks: u8,
+    /// the fraction of the shoe dealt before the cut card comes out
+    penetration: f64,
+    shuffling: Shuffling,
+    /// the running count of all the cards seen since the last shuffle
+    pub count: i32,
+    /// the system the cards are counted with, Hi-Lo unless set otherwise
+    pub counting: &'static dyn CountingSystem,
+    /// how many times the discards had been shuffled back in when the current round started
+    reshuffles: usize,
+}
+
+impl Shoe {
+    /// returns a new shuffled shoe of the specified number of decks, with the cut card placed
+    /// after the specified fraction of the shoe (between 0 and 1)
+    pub fn new(decks: u8, penetration: f64) -> Shoe {
+        return Shoe { deck: Shoe::shuffled_deck(decks), decks, penetration, shuffling: Shuffling::CutCard, count: 0, counting: &HiLo, reshuffles: 0 };
+    }
+
+    /// returns a new shoe of the specified number of decks in a continuous shuffling machine
+    pub fn new_continuous(decks: u8) -> Shoe {
+        return Shoe { shuffling: Shuffling::Continuous, ..Shoe::new(decks, 1.0) };
+    }
+
+    /// returns a new shuffled shoe for the decks, penetration and shuffling of the table rules
+    pub fn from(rules: &TableRules) -> Shoe {
+        return match rules.shuffling {
+            Shuffling::CutCard => Shoe::new(rules.decks, rules.penetration),
+            Shuffling::Continuous => Shoe::new_continuous(rules.decks)
+        };
+    }
+
+    /// starts a new round from the shoe, reshuffling first if the cut card has come out
+    pub fn new_round(&mut self, rules: &TableRules) -> Result<RoundState, RoundError> {
+        self.shuffle_if_due();
+
+        self.reshuffles = self.deck.reshuffles;
+        return RoundState::new_with_count(&mut self.deck, rules, self.counting, self.count);
+    }
+
+    /// discards the cards of a finished round and keeps its running count for the next one,
+    /// unless the discards had to be shuffled back in during the round, as the count starts again then.
+    /// A continuous shuffler takes the cards back at random positions, so the count always starts again.
+    pub fn end_round(&mut self, round_state: &RoundState) {
+        match self.shuffling {
+            Shuffling::CutCard => {
+                self.deck.discard();
+                self.count = if self.deck.reshuffles == self.reshuffles { round_state.count } else { self.initial_count() };
+            }
+
+            Shuffling::Continuous => {
+                self.deck.reinsert();
+                self.count = self.initial_count();
+            }
+        }
+    }
+
+    /// the cards left in the shoe, which the current round is dealt from
+    pub fn deck(&mut self) -> &mut Deck {
+        return &mut self.deck;
+    }
+
+    /// returns how many cards have been dealt since the last shuffle, less any discards shuffled back in
+    pub fn dealt(&self) -> usize {
+        return self.size() - self.deck.len();
+    }
+
+    /// returns how many decks the shoe is made of
+    pub fn decks(&self) -> u8 {
+        return self.decks;
+    }
+
+    /// returns how many cards are left in the shoe
+    pub fn cards_remaining(&self) -> usize {
+        return self.deck.len();
+    }
+
+    /// returns how many decks the cards left in the shoe make up
+    pub fn decks_remaining(&self) -> f64 {
+        return self.deck.decks_remaining();
+    }
+
+    /// returns the running count divided by the decks left in the shoe, rounded as specified
+    pub fn true_count(&self, rounding: Rounding) -> i32 {
+        return rounding.true_count(self.count, self.counting, self.cards_remaining());
+    }
+
+    /// returns true once the cut card has come out, so the shoe is reshuffled before the next round
+    pub fn cut_card_reached(&self) -> bool {
+        return self.shuffling == Shuffling::CutCard && self.dealt() as f64 >= self.penetration * self.size() as f64;
+    }
+
+    /// reshuffles the shoe if the cut card has come out, so that the bet on the next round is placed on the count it will be dealt from
+    pub fn shuffle_if_due(&mut self) {
+        if self.cut_card_reached() {
+            self.reshuffle();
+        }
+    }
+
+    /// gathers all the cards, shuffles them and resets the count
+    pub fn reshuffle(&mut self) {
+        self.deck = Shoe::shuffled_deck(self.decks);
+        self.count = self.initial_count();
+    }
+
+    /// counts the cards with the specified system from now on, starting again from its initial count
+    pub fn set_counting(&mut self, counting: &'static dyn CountingSystem) {
+        self.counting = counting;
+        self.count = self.initial_count();
+    }
+
+    /// the running count of a freshly shuffled shoe for the counting system
+    pub fn initial_count(&self) -> i32 {
+        return self.counting.initial_count(self.decks);
+    }
+
+    fn shuffled_deck(decks: u8) -> Deck {
+        let mut deck = Deck::new_shuffled_decks(decks);
+        deck.exhaustion = Exhaustion::ReshuffleDiscards;
+        return deck;
+    }
+
+    fn size(&self) -> usize {
+        return 52 * self.decks as usize;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::counting::KO;
+    use crate::round::Insurance;
+    use super::*;
+
+    //deals a round from the shoe and stands on it, declining insurance, without ending the round
+    fn stand_round(shoe: &mut Shoe, rules: &TableRules) -> RoundState {
+        let mut round_state = shoe.new_round(rules).unwrap();
+        if round_state.insurance == Insurance::Offered {
+            round_state = round_state.insure(false, shoe.deck()).unwrap();
+        }
+        if !round_state.finished() {
+            round_state = round_state.stand(shoe.deck()).unwrap();
+        }
+        assert!(round_state.finished());
+        return round_state;
+    }
+
+    #[test]
+    fn test_new_shoe() {
+        let shoe = Shoe::new(6, 0.75);
+
+        assert_eq!(0, shoe.dealt());
+        assert_eq!(0, shoe.count);
+        assert!(!shoe.cut_card_reached());
+        assert_eq!(312, shoe.cards_remaining());
+        assert_eq!(6.0, shoe.decks_remaining());
+    }
+
+    #[test]
+    fn test_shoe_true_count() {
+        let mut shoe = Shoe::new(2, 0.75);
+        for _ in 0..26 {
+            shoe.deck().deal();
+        }
+        shoe.count = 7;
+
+        assert_eq!(78, shoe.cards_remaining());
+        assert_eq!(4, shoe.true_count(Rounding::Floor));
+        shoe.count = -7;
+        assert_eq!(-5, shoe.true_count(Rounding::Floor));
+        assert_eq!(-4, shoe.true_count(Rounding::Truncate));
+    }
+
+    #[test]
+    fn test_shoe_persists_across_rounds() {
+        let rules = TableRules { decks: 2, ..TableRules::default() };
+        let mut shoe = Shoe::from(&rules);
+
+        let round_state = stand_round(&mut shoe, &rules);
+        shoe.end_round(&round_state);
+        let dealt = shoe.dealt();
+        assert!(dealt >= 3);
+
+        assert_eq!(round_state.count, shoe.count);
+
+        //the next round starts from the count of the previous one, and at most four cards of it are seen before acting
+        shoe.count = 7;
+        let round_state = shoe.new_round(&rules).unwrap();
+        assert!(shoe.dealt() >= dealt + 3);
+        assert!((3..=11).contains(&round_state.count));
+    }
+
+    #[test]
+    fn test_cut_card_reshuffles_and_resets_count() {
+        let rules = TableRules { decks: 1, penetration: 0.5, ..TableRules::default() };
+        let mut shoe = Shoe::from(&rules);
+
+        while !shoe.cut_card_reached() {
+            shoe.deck().deal();
+        }
+        shoe.count = 5;
+
+        let round_state = shoe.new_round(&rules).unwrap();
+        assert_eq!(0, shoe.count);
+        assert!(shoe.dealt() <= 4);
+        assert!(round_state.count.abs() <= 4);
+    }
+
+    #[test]
+    fn test_continuous_shuffler() {
+        let rules = TableRules { decks: 2, shuffling: Shuffling::Continuous, ..TableRules::default() };
+        let mut shoe = Shoe::from(&rules);
+
+        for _ in 0..100 {
+            let round_state = stand_round(&mut shoe, &rules);
+            shoe.end_round(&round_state);
+
+            //the cards of the round went back in, and with them the information the count carried
+            assert_eq!(0, shoe.dealt());
+            assert_eq!(0, shoe.count);
+            assert!(!shoe.cut_card_reached());
+        }
+    }
+
+    #[test]
+    fn test_shoe_never_runs_out_of_cards() {
+        //without a cut card the rounds keep running into the end of the shoe
+        let rules = TableRules { decks: 1, penetration: 1.0, ..TableRules::default() };
+        let mut shoe = Shoe::from(&rules);
+
+        for _ in 0..1000 {
+            let mut round_state = shoe.new_round(&rules).unwrap();
+            if round_state.insurance == Insurance::Offered {
+                round_state = round_state.insure(false, shoe.deck()).unwrap();
+            }
+            while !round_state.finished() {
+                round_state = round_state.hit(shoe.deck()).unwrap();
+            }
+            shoe.end_round(&round_state);
+        }
+    }
+
+    #[test]
+    fn test_unbalanced_count_starts_below_zero() {
+        let rules = TableRules { decks: 6, ..TableRules::default() };
+        let mut shoe = Shoe::from(&rules);
+        shoe.set_counting(&KO);
+        assert_eq!(-20, shoe.count);
+
+        let round_state = shoe.new_round(&rules).unwrap();
+        assert!((-24..=-16).contains(&round_state.count));
+
+        shoe.reshuffle();
+        assert_eq!(-20, shoe.count);
+    }
+}
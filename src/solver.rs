@@ -0,0 +1,351 @@
+use std::collections::HashMap;
+
+use crate::blackjack_agent::{BlackjackAction, BlackjackState};
+use crate::dealer::{add, DealerOutcomes, INFINITE_DECK};
+use crate::deck::Deck;
+use crate::qtable::{QTable, StateAction};
+use crate::rules::{Shuffling, Surrender, TableRules};
+
+/// Works out the expected value of every action in every total-dependent state for an infinite deck, instead of sampling it.
+/// Each card is drawn with the same probabilities however many have been dealt, so the cards of the round are never
+/// taken out: the values are exact for an infinite deck, close for a continuously shuffled shoe of many decks
+/// and off by the effect of removing the dealt cards for a few decks, whatever the number of decks in the rules.
+/// A split hand is played out without splitting it again. Early surrender is valued like late surrender.
+#[derive(Debug, Clone)]
+pub struct InfiniteDeckSolver {
+    rules: TableRules,
+    /// the probability of drawing each value, from the ace to the ten
+    probabilities: [f64; 10],
+    /// what the dealer ends up with for each up card, indexed by its value (an ace is 11)
+    dealer: [DealerOutcomes; 12],
+    /// the values of playing on with hits and stands only, for each up card
+    values: [HandValues; 12],
+}
+
+/// The best value of a hand that can only hit or stand, for each hard and soft total
+#[derive(Debug, Copy, Clone)]
+struct HandValues {
+    hard: [f64; 22],
+    soft: [f64; 22],
+}
+
+impl Default for HandValues {
+    fn default() -> HandValues {
+        return HandValues { hard: [0.0; 22], soft: [0.0; 22] };
+    }
+}
+
+impl InfiniteDeckSolver {
+    /// returns a solver for an infinite deck, where a ten-valued card is four times as likely as any other
+    pub fn new(rules: &TableRules) -> InfiniteDeckSolver {
+        return InfiniteDeckSolver::with_probabilities(rules, INFINITE_DECK);
+    }
+
+    /// returns a solver drawing the cards in the proportions they are left in the deck, such as a shoe rich in tens,
+    /// as if the deck were made of endless copies of those cards
+    pub fn from_deck(rules: &TableRules, deck: &Deck) -> InfiniteDeckSolver {
+        let composition = deck.composition();
+        let cards = usize::max(composition.iter().sum(), 1) as f64;
+        return InfiniteDeckSolver::with_probabilities(rules, composition.map(|count| count as f64 / cards));
+    }
+
+    /// returns a solver drawing each value, from the ace to the ten, with the specified probability
+    pub fn with_probabilities(rules: &TableRules, probabilities: [f64; 10]) -> InfiniteDeckSolver {
+        let mut solver = InfiniteDeckSolver { rules: *rules, probabilities, dealer: [DealerOutcomes::default(); 12], values: [HandValues::default(); 12] };
+        for up in 2..=11 {
+            solver.dealer[up] = DealerOutcomes::faced(up as u8, rules, &probabilities);
+            solver.values[up] = solver.hand_values(up as u8);
+        }
+        return solver;
+    }
+
+    /// returns the rules of the game the solver's values come closest to, for the learners to be trained on
+    /// before their values are compared: a continuous shuffler of eight decks, where the cards dealt hardly change the odds
+    pub fn closest_game(rules: &TableRules) -> TableRules {
+        return TableRules { decks: 8, shuffling: Shuffling::Continuous, ..*rules };
+    }
+
+    /// returns the probabilities of the dealer's final hand for the up card (an ace is 11)
+    pub fn dealer_outcomes(&self, up: u8) -> DealerOutcomes {
+        return self.dealer[up as usize];
+    }
+
+    /// returns the value of standing on the total
+    pub fn stand(&self, total: u8, up: u8) -> f64 {
+        return self.dealer[up as usize].stand(total);
+    }
+
+    /// returns the value of hitting the hand once, and then playing on as well as possible without doubling or splitting
+    pub fn hit(&self, total: u8, soft: bool, up: u8) -> f64 {
+        let values = &self.values[up as usize];
+        return self.draw(total, soft, |total, soft| InfiniteDeckSolver::value_of(values, total, soft));
+    }
+
+    /// returns the value of doubling the stake and drawing exactly one card
+    pub fn double(&self, total: u8, soft: bool, up: u8) -> f64 {
+        return 2.0 * self.draw(total, soft, |total, _| self.stand(total, up));
+    }
+
+    /// returns the value of splitting a pair of cards of the value (an ace is 1) into two hands. Split aces only get
+    /// one more card each, other hands are played on as well as possible, doubling if the rules allow it after a split.
+    pub fn split(&self, card: u8, up: u8) -> f64 {
+        let (total, soft) = add(0, false, card);
+        let hand = if card == 1 {
+            self.draw(total, soft, |total, _| self.stand(total, up))
+        } else {
+            self.draw(total, soft, |total, soft| {
+                let mut value = f64::max(self.stand(total, up), self.hit(total, soft, up));
+                if self.rules.double_after_split && self.rules.double.allows(total, soft) {
+                    value = f64::max(value, self.double(total, soft, up));
+                }
+                value
+            })
+        };
+        return 2.0 * hand;
+    }
+
+    /// returns the value of surrendering half of the stake. Without a hole card, late surrender
+    /// still loses the whole stake to a dealer natural.
+    pub fn surrender(&self, up: u8) -> f64 {
+        return -0.5 - 0.5 * self.dealer[up as usize].natural;
+    }
+
+    /// returns the exact values of all the actions in all the total-dependent states, as a table to compare the learnt ones with
+    pub fn q_table(&self) -> QTable<BlackjackState, BlackjackAction> {
+        let mut q_table = QTable::new(0.0);
+        for dealer in 2u8..=11 {
+            for player in 5u8..=21 {
+                self.add_state(&mut q_table, BlackjackState { player, dealer, ace: false, pair: false, composition: None, count: None });
+            }
+            for player in 13u8..=21 {
+                self.add_state(&mut q_table, BlackjackState { player, dealer, ace: true, pair: false, composition: None, count: None });
+            }
+            if self.rules.max_splits > 0 {
+                for card in 1u8..=10 {
+                    let (player, ace) = if card == 1 { (12, true) } else { (2 * card, false) };
+                    self.add_state(&mut q_table, BlackjackState { player, dealer, ace, pair: true, composition: None, count: None });
+                }
+            }
+        }
+        return q_table;
+    }
+
+    /// returns the exact values of the actions that are available in the state on the first two cards
+    pub fn action_values(&self, state: &BlackjackState) -> Vec<(BlackjackAction, f64)> {
+        let (total, soft, up) = (state.player, state.ace, state.dealer);
+        if total == 21 {
+            return vec![(BlackjackAction::Stand, self.stand(total, up))];
+        }
+
+        let mut values = vec![(BlackjackAction::Hit, self.hit(total, soft, up))];
+        if total >= 12 {
+            values.push((BlackjackAction::Stand, self.stand(total, up)));
+        }
+        if self.rules.double.allows(total, soft) {
+            values.push((BlackjackAction::Double, self.double(total, soft, up)));
+        }
+        if state.pair {
+            let card = if soft { 1 } else { total / 2 };
+            values.push((BlackjackAction::Split, self.split(card, up)));
+        }
+        if self.rules.surrender != Surrender::None {
+            values.push((BlackjackAction::Surrender, self.surrender(up)));
+        }
+        return values;
+    }
+
+    fn add_state(&self, q_table: &mut QTable<BlackjackState, BlackjackAction>, agent_state: BlackjackState) {
+        for (action, value) in self.action_values(&agent_state) {
+            q_table.update_value(&StateAction { agent_state, action }, value);
+        }
+    }
+
+    /// returns the expected value of drawing a card to the hand, given the value of the hand it makes
+    fn draw<F>(&self, total: u8, soft: bool, value: F) -> f64
+        where F: Fn(u8, bool) -> f64 {
+        return self.probabilities.iter().enumerate()
+            .map(|(i, probability)| {
+                let (total, soft) = add(total, soft, i as u8 + 1);
+                probability * if total > 21 { -1.0 } else { value(total, soft) }
+            })
+            .sum();
+    }
+
+    fn value_of(values: &HandValues, total: u8, soft: bool) -> f64 {
+        return if soft { values.soft[total as usize] } else { values.hard[total as usize] };
+    }
+
+    /// works out the best value of hitting or standing on every total, from the ones the others depend on:
+    /// hard totals from 11 up only draw to higher hard totals, soft ones to higher soft or hard totals from 12 up,
+    /// and the lowest hard totals to any of those
+    fn hand_values(&self, up: u8) -> HandValues {
+        let mut values = HandValues::default();
+        let best = |values: &HandValues, total: u8, soft: bool| {
+            let hit = self.draw(total, soft, |total, soft| InfiniteDeckSolver::value_of(values, total, soft));
+            f64::max(self.stand(total, up), hit)
+        };
+
+        for total in (11..=21).rev() {
+            values.hard[total as usize] = best(&values, total, false);
+        }
+        for total in (12..=21).rev() {
+            values.soft[total as usize] = best(&values, total, true);
+        }
+        for total in (2..=10).rev() {
+            values.hard[total as usize] = best(&values, total, false);
+        }
+        return values;
+    }
+}
+
+/// How far learnt values are from the exact ones.
+#[derive(Debug, Copy, Clone)]
+pub struct Comparison {
+    /// how many state actions both have a value for
+    pub compared: usize,
+    pub mean_error: f64,
+    pub max_error: f64,
+    /// how many states the best learnt action is the exact best among the same actions, and how many states were compared
+    pub agreeing: usize,
+    pub states: usize,
+    /// how much the best learnt value of a state is above the exact value of the state on average,
+    /// which taking the maximum of noisy estimates makes positive
+    pub mean_bias: f64,
+}
+
+/// compares the learnt values of the total-dependent states with the exact ones, over the actions both know
+pub fn compare(learnt: &HashMap<BlackjackState, HashMap<BlackjackAction, f64>>, exact: &QTable<BlackjackState, BlackjackAction>) -> Comparison {
+    let mut comparison = Comparison { compared: 0, mean_error: 0.0, max_error: 0.0, agreeing: 0, states: 0, mean_bias: 0.0 };
+    for (state, values) in learnt {
+        let exact_actions = exact.get_actions(state);
+        let actions: Vec<BlackjackAction> = values.keys().filter(|action| exact_actions.contains(action)).copied().collect();
+        if actions.is_empty() {
+            continue;
+        }
+
+        for action in &actions {
+            let error = (values[action] - exact.get_value(&StateAction { agent_state: *state, action: *action })).abs();
+            comparison.compared += 1;
+            comparison.mean_error += (error - comparison.mean_error) / comparison.compared as f64;
+            comparison.max_error = f64::max(comparison.max_error, error);
+        }
+
+        let best_learnt = actions.iter().max_by(|a, b| values[a].partial_cmp(&values[b]).unwrap());
+        let best_exact = exact.select_greedy_action_among(state, &actions);
+        if best_learnt == best_exact.as_ref() {
+            comparison.agreeing += 1;
+        }
+        comparison.states += 1;
+
+        let bias = values[best_learnt.unwrap()] - exact.get_value(&StateAction { agent_state: *state, action: best_exact.unwrap() });
+        comparison.mean_bias += (bias - comparison.mean_bias) / comparison.states as f64;
+    }
+    return comparison;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rules::HoleCard;
+    use super::*;
+
+    fn state(player: u8, dealer: u8, ace: bool, pair: bool) -> BlackjackState {
+        return BlackjackState { player, dealer, ace, pair, composition: None, count: None };
+    }
+
+    fn best(q_table: &QTable<BlackjackState, BlackjackAction>, state: BlackjackState) -> BlackjackAction {
+        return q_table.select_greedy_action(&state).unwrap();
+    }
+
+    #[test]
+    fn test_dealer_outcomes_faced() {
+        //the player only faces a natural when there is no hole card to peek at
+        let solver = InfiniteDeckSolver::new(&TableRules { hole_card: HoleCard::NoHoleCard, ..TableRules::default() });
+        assert!((solver.dealer_outcomes(10).natural - 1.0 / 13.0).abs() < 1e-9);
+        let peeking = InfiniteDeckSolver::new(&TableRules::default());
+        assert_eq!(0.0, peeking.dealer_outcomes(10).natural);
+        assert_eq!(0.0, peeking.dealer_outcomes(11).natural);
+    }
+
+    #[test]
+    fn test_stand_values() {
+        let solver = InfiniteDeckSolver::new(&TableRules::default());
+        //standing on 21 against a 6 only pushes when the dealer makes 21 too
+        let outcomes = solver.dealer_outcomes(6);
+        assert!((solver.stand(21, 6) - (1.0 - outcomes.totals[4])).abs() < 1e-9);
+        //below 17 only a dealer bust wins
+        assert!((solver.stand(12, 6) - (2.0 * outcomes.bust - 1.0)).abs() < 1e-9);
+        assert_eq!(-1.0, solver.stand(22, 6));
+    }
+
+    #[test]
+    fn test_optimal_policy_is_basic_strategy() {
+        let q_table = InfiniteDeckSolver::new(&TableRules::default()).q_table();
+
+        assert_eq!(BlackjackAction::Hit, best(&q_table, state(16, 10, false, false)));
+        assert_eq!(BlackjackAction::Stand, best(&q_table, state(16, 6, false, false)));
+        assert_eq!(BlackjackAction::Stand, best(&q_table, state(12, 4, false, false)));
+        assert_eq!(BlackjackAction::Hit, best(&q_table, state(12, 3, false, false)));
+        assert_eq!(BlackjackAction::Double, best(&q_table, state(11, 6, false, false)));
+        assert_eq!(BlackjackAction::Double, best(&q_table, state(10, 9, false, false)));
+        assert_eq!(BlackjackAction::Hit, best(&q_table, state(18, 9, true, false)));
+        assert_eq!(BlackjackAction::Double, best(&q_table, state(18, 4, true, false)));
+        assert_eq!(BlackjackAction::Split, best(&q_table, state(16, 10, false, true)));
+        assert_eq!(BlackjackAction::Split, best(&q_table, state(12, 6, true, true)));
+        assert_eq!(BlackjackAction::Stand, best(&q_table, state(20, 6, false, true)));
+        assert_eq!(BlackjackAction::Stand, best(&q_table, state(18, 7, false, true)));
+    }
+
+    #[test]
+    fn test_surrender() {
+        let late = TableRules { surrender: Surrender::Late, ..TableRules::default() };
+        let q_table = InfiniteDeckSolver::new(&late).q_table();
+        assert_eq!(BlackjackAction::Surrender, best(&q_table, state(16, 10, false, false)));
+        assert_eq!(BlackjackAction::Stand, best(&q_table, state(17, 10, false, false)));
+
+        let enhc = InfiniteDeckSolver::new(&TableRules { hole_card: HoleCard::NoHoleCard, ..late });
+        assert!((enhc.surrender(10) - (-0.5 - 0.5 / 13.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_rich_deck() {
+        //with only tens left 10 against a 10 always pushes by standing on 20 and 20 wins against a 6 made 16
+        let deck = Deck::new_rigged(&[10; 20]);
+        let solver = InfiniteDeckSolver::from_deck(&TableRules::default(), &deck);
+        assert_eq!(0.0, solver.stand(20, 10));
+        assert_eq!(1.0, solver.stand(20, 6));
+        assert_eq!(2.0, solver.double(10, false, 6));
+        //an ace always has a natural, which the peek settles, so there is nothing left to play against
+        assert_eq!(0.0, solver.stand(20, 11));
+        assert!(solver.q_table().get_all_values().iter().all(|(_, value)| !value.is_nan()));
+    }
+
+    #[test]
+    fn test_closest_game() {
+        let rules = TableRules { dealer_hits_soft_17: true, surrender: Surrender::Late, ..TableRules::default() };
+        let game = InfiniteDeckSolver::closest_game(&rules);
+        assert_eq!(Shuffling::Continuous, game.shuffling);
+        assert_eq!(8, game.decks);
+        assert_eq!(TableRules { decks: rules.decks, shuffling: rules.shuffling, ..game }, rules);
+    }
+
+    #[test]
+    fn test_compare() {
+        let exact = InfiniteDeckSolver::new(&TableRules::default()).q_table();
+        let hard_16 = state(16, 10, false, false);
+        let stand = exact.get_value(&StateAction { agent_state: hard_16, action: BlackjackAction::Stand });
+        let hit = exact.get_value(&StateAction { agent_state: hard_16, action: BlackjackAction::Hit });
+
+        let mut learnt = HashMap::new();
+        learnt.insert(hard_16, HashMap::from([(BlackjackAction::Stand, stand + 0.1), (BlackjackAction::Hit, hit)]));
+        let comparison = compare(&learnt, &exact);
+
+        assert_eq!(2, comparison.compared);
+        assert!((comparison.mean_error - 0.05).abs() < 1e-9);
+        assert!((comparison.max_error - 0.1).abs() < 1e-9);
+        assert_eq!(1, comparison.states);
+        assert_eq!(0, comparison.agreeing);
+        //the overestimated stand looks better than the exact best value, the hit
+        assert!(hit > stand);
+        assert!((comparison.mean_bias - (stand + 0.1 - hit)).abs() < 1e-9);
+    }
+}